@@ -0,0 +1,357 @@
+use music_generator::{
+    melody_type::{MelodyFormParseError, SynthwaveMelodyType},
+    midi::{
+        generator::arrangement::{Arrangement, ArrangementParseError},
+        key_list, melody_types, mode_list,
+        resolution::Resolution,
+        scale_list,
+        time_signature::TimeSignature,
+    },
+};
+
+use rust_music_theory::{
+    note::PitchClass,
+    scale::{Mode, ScaleType},
+};
+
+use std::{
+    fmt::{Debug, Display, Formatter},
+    ops::RangeInclusive,
+    path::PathBuf,
+};
+
+/// Range of BPM values that can be passed with `--bpm`
+const BPM_RANGE: RangeInclusive<u64> = 40..=240;
+
+/// Range of both desired fitness and mutation rate of the genetic algorithm
+pub const GENETIC_RATE_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+
+pub const USAGE: &str = "\
+Usage: music_generator [OPTIONS]
+
+Options:
+    --key <KEY>        Key of the melody (e.g. C, Ds, F#)
+    --scale <SCALE>    Scale of the melody (e.g. Diatonic, HarmonicMinor)
+    --mode <MODE>      Mode of the scale (e.g. Aeolian, Dorian)
+    --type <TYPE>      Melody type (e.g. ABAB, AAAB, ABAC, AABA, ABCD, AABB, ABAB', CallAndResponse)
+                       or the custom form of 4 letters (e.g. AAB'A), where ' marks
+                       the variation that resolves to the tonic, + / - a scale step up / down,
+                       ~ inversion, < retrograde, * augmentation, / diminution, ^ ornaments, . truncation.
+                       Degrees of the 4-th and 8-th bars' endings may follow after | (e.g. ABAC|_,1),
+                       _ for no cadence [default: 5,1]
+    --bpm <BPM>        Tempo in range 40..=240. Random in 90..=120 if missing
+    --time-signature <SIGNATURE>
+                       Time signature (e.g. 3/4, 6/8, 5/4, 7/8) [default: 4/4]
+    --ppq <PPQ>        Time base of the output file in ticks per quarter (e.g. 96, 480, 960) [default: 128]
+    --out <DIR>        Output folder for the generated files [default: ./generated]
+    --seed <SEED>      Seed of the random generator. Same seed and settings produce the same file
+    --arrangement <SECTIONS>
+                       Generates the whole song instead of the 8 bar loop.
+                       Either `default` or comma separated sections `kind[:bars[:instruments]]`,
+                       where kind is intro, verse, chorus, bridge or outro and
+                       instruments are letters l (lead), h (harmony), a (arpeggio), b (bass), d (drums).
+                       E.g. intro:4:ha,verse:8,chorus:8:lhabd,outro:4
+    --genetic          Generates the lead with the genetic algorithm
+                       from the sample files (only in 4/4)
+    --fitness <FITNESS>
+                       Desired fitness of the genetic algorithm in range 0..=1
+    --mutation-rate <RATE>
+                       Mutation rate of the genetic algorithm in range 0..=1
+    --samples <DIR>    Folder with the sample files of the genetic algorithm [default: ./genetic_samples]
+    -h, --help         Prints this message
+
+Missing key, scale, mode and melody type are requested interactively.
+With --genetic, missing fitness and mutation rate are also requested";
+
+/// Generation settings parsed from the command line arguments.
+/// Every missing value is either requested interactively or generated
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CliArgs {
+    pub key: Option<PitchClass>,
+    pub scale: Option<ScaleType>,
+    pub mode: Option<Mode>,
+    pub melody_type: Option<SynthwaveMelodyType>,
+    pub bpm: Option<u64>,
+    pub time_signature: Option<TimeSignature>,
+    pub resolution: Option<Resolution>,
+    pub out: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub arrangement: Option<Arrangement>,
+    pub genetic: bool,
+    pub desired_fitness: Option<f32>,
+    pub mutation_rate: Option<f32>,
+    pub samples: Option<PathBuf>,
+}
+
+/// Errors that may occur while parsing the command line arguments
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CliError {
+    /// Help message was requested with `-h` or `--help`
+    Help,
+
+    /// Flag is not supported by the application
+    UnknownFlag(String),
+
+    /// Flag was passed without any value
+    MissingValue(String),
+
+    /// Value is not in the list of supported values
+    InvalidValue {
+        flag: String,
+        value: String,
+        expected: Vec<String>,
+    },
+
+    /// BPM is either not a number or not in the [BPM_RANGE]
+    InvalidBpm(String),
+
+    /// Time signature is not in form `numerator/denominator`,
+    /// numerator is not in range 2..=16 or denominator is not 2, 4 or 8
+    InvalidTimeSignature(String),
+
+    /// PPQ is not a number in range 1..=32767
+    InvalidPpq(String),
+
+    /// Seed is not an unsigned 64-bit number
+    InvalidSeed(String),
+
+    /// Melody type is neither the named form nor the custom one
+    InvalidMelodyType(MelodyFormParseError),
+
+    /// Arrangement can not be parsed
+    InvalidArrangement(ArrangementParseError),
+
+    /// Desired fitness is not a number in the [GENETIC_RATE_RANGE]
+    InvalidFitness(String),
+
+    /// Mutation rate is not a number in the [GENETIC_RATE_RANGE]
+    InvalidMutationRate(String),
+
+    /// Genetic algorithm learns from the samples in 4/4,
+    /// so other time signatures are not supported
+    GeneticTimeSignature(TimeSignature),
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Help => write!(f, "{USAGE}"),
+            CliError::UnknownFlag(flag) => write!(f, "Unknown flag '{flag}'"),
+            CliError::MissingValue(flag) => write!(f, "Flag '{flag}' requires a value"),
+
+            CliError::InvalidValue {
+                flag,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value '{value}' for '{flag}'. Expected one of: {}",
+                expected.join(", ")
+            ),
+
+            CliError::InvalidBpm(value) => write!(
+                f,
+                "Invalid BPM '{value}'. Expected a number in range {}..={}",
+                BPM_RANGE.start(),
+                BPM_RANGE.end()
+            ),
+
+            CliError::InvalidTimeSignature(value) => write!(
+                f,
+                "Invalid time signature '{value}'. Expected numerator in range 2..=16 \
+                and denominator 2, 4 or 8 (e.g. 3/4, 6/8)"
+            ),
+
+            CliError::InvalidPpq(value) => write!(
+                f,
+                "Invalid PPQ '{value}'. Expected a number in range 1..=32767"
+            ),
+
+            CliError::InvalidSeed(value) => write!(
+                f,
+                "Invalid seed '{value}'. Expected a number in range 0..={}",
+                u64::MAX
+            ),
+
+            CliError::InvalidMelodyType(err) => write!(
+                f,
+                "Invalid melody type. {err}. Named types: {}",
+                melody_types()
+                    .iter()
+                    .map(|melody_type| melody_type.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+
+            CliError::InvalidArrangement(err) => write!(f, "Invalid arrangement. {err}"),
+
+            CliError::InvalidFitness(value) => write!(
+                f,
+                "Invalid fitness '{value}'. Expected a number in range 0..=1"
+            ),
+
+            CliError::InvalidMutationRate(value) => write!(
+                f,
+                "Invalid mutation rate '{value}'. Expected a number in range 0..=1"
+            ),
+
+            CliError::GeneticTimeSignature(time_signature) => write!(
+                f,
+                "Time signature {time_signature} is not supported by the genetic algorithm. \
+                Only 4/4 leads are generated"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl CliArgs {
+    /// Parses arguments (without the program's name).
+    /// Both `--flag value` and `--flag=value` forms are supported
+
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, CliError> {
+        let mut cli_args = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                return Err(CliError::Help);
+            }
+
+            if arg == "--genetic" {
+                cli_args.genetic = true;
+                continue;
+            }
+
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+
+            let value = || {
+                value
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliError::MissingValue(flag.clone()))
+            };
+
+            match flag.as_str() {
+                "--key" => cli_args.key = Some(parse_from_list(&flag, &value()?, key_list())?),
+                "--scale" => {
+                    cli_args.scale = Some(parse_from_list(&flag, &value()?, scale_list())?)
+                }
+                "--mode" => cli_args.mode = Some(parse_from_list(&flag, &value()?, mode_list())?),
+                "--type" => {
+                    cli_args.melody_type =
+                        Some(value()?.parse().map_err(CliError::InvalidMelodyType)?)
+                }
+                "--bpm" => cli_args.bpm = Some(parse_bpm(&value()?)?),
+                "--time-signature" => {
+                    cli_args.time_signature = Some(parse_time_signature(&value()?)?)
+                }
+                "--ppq" => cli_args.resolution = Some(parse_ppq(&value()?)?),
+                "--out" => cli_args.out = Some(PathBuf::from(value()?)),
+                "--seed" => cli_args.seed = Some(parse_seed(&value()?)?),
+                "--arrangement" => {
+                    cli_args.arrangement =
+                        Some(value()?.parse().map_err(CliError::InvalidArrangement)?)
+                }
+                "--fitness" => {
+                    cli_args.desired_fitness =
+                        Some(parse_genetic_rate(&value()?, CliError::InvalidFitness)?)
+                }
+                "--mutation-rate" => {
+                    cli_args.mutation_rate = Some(parse_genetic_rate(
+                        &value()?,
+                        CliError::InvalidMutationRate,
+                    )?)
+                }
+                "--samples" => cli_args.samples = Some(PathBuf::from(value()?)),
+                _ => return Err(CliError::UnknownFlag(flag)),
+            }
+        }
+
+        match cli_args.time_signature {
+            Some(time_signature) if cli_args.genetic && time_signature != TimeSignature::COMMON => {
+                Err(CliError::GeneticTimeSignature(time_signature))
+            }
+
+            _ => Ok(cli_args),
+        }
+    }
+}
+
+/// Searches value in the list of supported values.
+/// Value is compared with the debug representation
+/// of the item, case is ignored. Sharps may be written
+/// either as `s` or as `#` (`Ds` and `D#` are the same key)
+
+#[inline]
+fn parse_from_list<T: Debug + Clone>(flag: &str, value: &str, list: Vec<T>) -> Result<T, CliError> {
+    let normalized = value.replace('#', "s");
+
+    list.iter()
+        .find(|item| normalized.eq_ignore_ascii_case(&format!("{:?}", item)))
+        .cloned()
+        .ok_or_else(|| CliError::InvalidValue {
+            flag: flag.to_string(),
+            value: value.to_string(),
+            expected: list.iter().map(|item| format!("{:?}", item)).collect(),
+        })
+}
+
+/// Parses BPM and checks that it lies in the [BPM_RANGE]
+
+#[inline]
+fn parse_bpm(value: &str) -> Result<u64, CliError> {
+    value
+        .parse::<u64>()
+        .ok()
+        .filter(|bpm| BPM_RANGE.contains(bpm))
+        .ok_or_else(|| CliError::InvalidBpm(value.to_string()))
+}
+
+/// Parses time signature written as `numerator/denominator`
+
+#[inline]
+fn parse_time_signature(value: &str) -> Result<TimeSignature, CliError> {
+    value
+        .parse::<TimeSignature>()
+        .map_err(|_| CliError::InvalidTimeSignature(value.to_string()))
+}
+
+/// Parses time base of the output file
+
+#[inline]
+fn parse_ppq(value: &str) -> Result<Resolution, CliError> {
+    value
+        .parse::<Resolution>()
+        .map_err(|_| CliError::InvalidPpq(value.to_string()))
+}
+
+/// Parses seed of the random generator
+
+#[inline]
+fn parse_seed(value: &str) -> Result<u64, CliError> {
+    value
+        .parse::<u64>()
+        .map_err(|_| CliError::InvalidSeed(value.to_string()))
+}
+
+/// Parses either desired fitness or mutation rate
+/// and checks that it lies in the [GENETIC_RATE_RANGE]
+
+#[inline]
+pub fn parse_genetic_rate<E>(value: &str, error: E) -> Result<f32, CliError>
+where
+    E: FnOnce(String) -> CliError,
+{
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|rate| GENETIC_RATE_RANGE.contains(rate))
+        .ok_or_else(|| error(value.to_string()))
+}
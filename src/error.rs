@@ -0,0 +1,96 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::PathBuf,
+};
+
+/// Result of the library's operations
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors that may occur during the parsing and the generation
+
+#[derive(Debug)]
+pub enum Error {
+    /// MIDI value is not in range 21..=128,
+    /// so it can not be converted to the [crate::notes::note::Note]
+    InvalidNote(u8),
+
+    /// Note of the given octave can not be converted
+    /// to the [crate::notes::note::Note]
+    InvalidOctave(u8),
+
+    /// Scale can not be constructed from the given key, scale type and mode
+    InvalidScale(String),
+
+    /// Scale does not contain any note
+    EmptyScale,
+
+    /// Note off event was received for the note
+    /// (MIDI value) that was not turned on
+    UnmatchedNoteOff(u8),
+
+    /// MIDI file can not be read or parsed
+    Midi { path: PathBuf, message: String },
+
+    /// There are no sample files to learn from
+    NoSamples,
+
+    /// Island of the genetic algorithm targets the sample
+    /// with the index out of the number of samples
+    InvalidIslandSample { sample: usize, samples: usize },
+
+    /// Threads that generate leads can not be spawned
+    ThreadPool(String),
+
+    /// Input / output error
+    Io(std::io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidNote(midi) => {
+                write!(f, "MIDI value {midi} is out of supported range 21..=128")
+            }
+
+            Error::InvalidOctave(octave) => {
+                write!(f, "Octave {octave} is out of supported range")
+            }
+
+            Error::InvalidScale(message) => write!(f, "Unable to construct scale: {message}"),
+            Error::EmptyScale => write!(f, "Scale does not contain any note"),
+
+            Error::UnmatchedNoteOff(midi) => {
+                write!(f, "Note off event for the note {midi} that was not on")
+            }
+
+            Error::Midi { path, message } => {
+                write!(f, "Unable to parse MIDI file {:?}: {message}", path)
+            }
+
+            Error::NoSamples => write!(f, "There are no sample MIDI files"),
+
+            Error::InvalidIslandSample { sample, samples } => write!(
+                f,
+                "Island targets sample {sample}, but there are only {samples} samples"
+            ),
+            Error::ThreadPool(message) => write!(f, "Unable to spawn threads: {message}"),
+            Error::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    #[inline]
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{
+    genetic::{
+        crossover::CrossoverMethod,
+        fitness::{FitnessWeights, SimilarityMode},
+        island::IslandModel,
+        mutation::{Mutation, MutationOperator},
+        selection::SelectionMethod,
+    },
+    midi::parser::midi_file_manager::DEFAULT_SAMPLES_PATH,
+    rules::RuleSet,
+};
+use std::{path::PathBuf, time::Duration};
+
+/// Hyperparameters and termination conditions of the genetic algorithm.
+/// Algorithm stops when either the desired fitness is reached,
+/// or the maximum number of generations is evaluated,
+/// or the time budget is spent, or the best fitness
+/// is not improved for the stagnation limit of generations
+///
+/// # Example
+/// ```
+/// use music_generator::genetic::{
+///     config::GeneticConfig,
+///     selection::{SelectionMethod, Tournament},
+/// };
+/// use std::time::Duration;
+///
+/// let config = GeneticConfig {
+///     population_size: 200,
+///     elitism: 2,
+///     selection: SelectionMethod::Tournament(Tournament { size: 3 }),
+///     time_budget: Some(Duration::from_secs(30)),
+///     ..GeneticConfig::new(0.8, 0.1)
+/// };
+///
+/// assert_eq!(config.max_generations, 100)
+/// ```
+
+#[derive(Clone, Debug)]
+pub struct GeneticConfig {
+    /// Folder with the sample leads to learn from
+    pub samples_path: PathBuf,
+
+    /// Fitness in range 0..=1 that is enough to stop the algorithm
+    pub desired_fitness: f32,
+
+    /// Weights of the fitness components
+    pub fitness_weights: FitnessWeights,
+
+    /// How leads are compared with the samples
+    pub similarity: SimilarityMode,
+
+    /// Rules of the fitness's rules component.
+    /// Phrase endings of the melody type's cadences
+    /// ([crate::rules::builtin::PhraseEndings]) are always checked in addition
+    pub rules: RuleSet,
+
+    /// Probability in range 0..=1 of every note's pitch to be mutated
+    pub mutation_rate: f32,
+
+    /// Rhythm, velocity and phrase mutations
+    /// that are applied after the pitch mutation
+    pub mutations: Vec<Mutation>,
+
+    /// Number of leads in every generation
+    pub population_size: usize,
+
+    /// Sub-populations that exchange their best leads.
+    /// Single population is evolved if it is not specified
+    pub islands: Option<IslandModel>,
+
+    /// Maximum number of generations after the initial one
+    pub max_generations: usize,
+
+    /// Number of the fittest leads that are moved
+    /// to the next generation without changes
+    pub elitism: usize,
+
+    /// Operator that selects parents of the next generation
+    pub selection: SelectionMethod,
+
+    /// Operator that produces children from the pairs of parents
+    pub crossover: CrossoverMethod,
+
+    /// Probability of one of the parents to be
+    /// added to the next generation along with their child
+    pub parent_survival_probability: f64,
+
+    /// Maximum time of the algorithm's work.
+    /// Runs with the time budget may not be reproduced
+    /// with the same seed, as the number of generations
+    /// depends on the machine
+    pub time_budget: Option<Duration>,
+
+    /// Maximum number of generations in a row
+    /// without improvement of the best fitness
+    pub stagnation_limit: Option<usize>,
+
+    /// Number of threads that generate and evaluate leads.
+    /// All available cores are used if it is not specified.
+    /// Seeded runs produce the same lead with any number of threads
+    pub threads: Option<usize>,
+}
+
+impl GeneticConfig {
+    /// Constructs config with the given desired fitness and mutation rate.
+    /// Other settings are default
+
+    #[inline]
+    pub fn new(desired_fitness: f32, mutation_rate: f32) -> Self {
+        Self {
+            desired_fitness,
+            mutation_rate,
+            ..Self::default()
+        }
+    }
+
+    /// All mutations of the algorithm, starting from the pitch mutation
+
+    #[inline]
+    pub fn mutation_pipeline(&self) -> Vec<Mutation> {
+        std::iter::once(Mutation::new(MutationOperator::Pitch, self.mutation_rate))
+            .chain(self.mutations.iter().copied())
+            .collect()
+    }
+}
+
+impl Default for GeneticConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            samples_path: PathBuf::from(DEFAULT_SAMPLES_PATH),
+            desired_fitness: 1.0,
+            fitness_weights: FitnessWeights::default(),
+            similarity: SimilarityMode::Nearest,
+            rules: RuleSet::fitness(),
+            mutation_rate: 0.1,
+            mutations: vec![
+                Mutation::new(MutationOperator::Split, 0.02),
+                Mutation::new(MutationOperator::Merge, 0.02),
+                Mutation::new(MutationOperator::Shift, 0.02),
+                Mutation::new(MutationOperator::Length, 0.02),
+                Mutation::new(MutationOperator::Velocity, 0.05),
+                Mutation::new(MutationOperator::Rest, 0.02),
+                Mutation::new(MutationOperator::Transpose, 0.05),
+            ],
+            population_size: 1000,
+            islands: None,
+            max_generations: 100,
+            elitism: 0,
+            selection: SelectionMethod::Roulette,
+            crossover: CrossoverMethod::Bar,
+            parent_survival_probability: 0.25,
+            time_budget: None,
+            stagnation_limit: None,
+            threads: None,
+        }
+    }
+}
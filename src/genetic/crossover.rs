@@ -1,6 +1,213 @@
-use crate::WithNextIterable;
+use crate::{
+    melody_type::SynthwaveMelodyType,
+    midi::{
+        generator::{with_starts_from_delays, with_starts_in_bars},
+        time_signature::{TimeSignature, STEP_TIME},
+    },
+    notes::note_data::{DeltaTime, NoteData},
+    WithNextIterable,
+};
+
 use itertools::Itertools;
-use rand::Rng;
+use rand::{seq::SliceRandom, Rng};
+
+/// Crossover operators that can be chosen in the [super::config::GeneticConfig]
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum CrossoverMethod {
+    /// Parts between random note indexes are taken from the parents alternately
+    /// (see [crossover]). Timing of the child may not fit the bars
+    Index,
+
+    /// Every part of the form (A, B, C) is taken from the random parent
+    /// (see [bar_crossover])
+    #[default]
+    Bar,
+
+    /// All bars are cut at the same random beats
+    /// (see [beat_crossover])
+    Beat,
+}
+
+impl CrossoverMethod {
+    /// Generates lead from two given leads
+    /// of the given melody type and time signature
+
+    #[inline]
+    pub fn crossover(
+        &self,
+        parent1: Vec<NoteData>,
+        parent2: Vec<NoteData>,
+        melody_type: SynthwaveMelodyType,
+        time_signature: TimeSignature,
+        rng: &mut impl Rng,
+    ) -> Vec<NoteData> {
+        match self {
+            CrossoverMethod::Index => crossover(parent1, parent2, rng),
+            CrossoverMethod::Bar => {
+                bar_crossover(parent1, parent2, melody_type, time_signature, rng)
+            }
+            CrossoverMethod::Beat => beat_crossover(parent1, parent2, time_signature, rng),
+        }
+    }
+}
+
+/// Generates lead from two given leads by taking whole bars from them.
+/// For every part of the melody type's form (A, B or C)
+/// the parent is chosen randomly, and all bars of this part
+/// are taken from the same parent, so the phrase structure is kept.
+/// Notes' positions are calculated from delays and lengths,
+/// child's starts and delays are derived from them again
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     genetic::crossover::bar_crossover,
+///     melody_type::SynthwaveMelodyType,
+///     midi::time_signature::TimeSignature,
+///     notes::{note::Note, note_data::NoteData},
+/// };
+///
+/// // Single note at the beginning of every bar
+/// let lead = |midi: u8| {
+///     (0..8)
+///         .map(|bar| {
+///             let note = Note::try_from(midi).unwrap();
+///             NoteData::new(note, 75, 0, 128, (bar > 0) as u32 * 384)
+///         })
+///         .collect::<Vec<_>>()
+/// };
+///
+/// let mut rng = rand::thread_rng();
+/// let child = bar_crossover(
+///     lead(60),
+///     lead(64),
+///     SynthwaveMelodyType::ABAB,
+///     TimeSignature::COMMON,
+///     &mut rng,
+/// );
+///
+/// assert_eq!(child.len(), 8);
+/// assert!(child.iter().all(|note| note.start() == 0));
+///
+/// // Both A bars and both B bars are taken from the same parent
+/// assert_eq!(child[0].note(), child[2].note());
+/// assert_eq!(child[1].note(), child[3].note())
+/// ```
+
+#[inline]
+pub fn bar_crossover(
+    parent1: Vec<NoteData>,
+    parent2: Vec<NoteData>,
+    melody_type: SynthwaveMelodyType,
+    time_signature: TimeSignature,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
+    let form = melody_type.form();
+
+    let parts_from_second = form
+        .iter()
+        .unique()
+        .map(|&part| (part, rng.gen_bool(0.5)))
+        .collect::<Vec<_>>();
+
+    let bars_from_second = form
+        .iter()
+        .map(|part| {
+            parts_from_second
+                .iter()
+                .any(|(p, from_second)| p == part && *from_second)
+        })
+        .collect::<Vec<_>>();
+
+    let bar_time = time_signature.bar_time();
+
+    merge_by_time(parent1, parent2, time_signature, |start| {
+        bars_from_second[(start / bar_time) as usize % bars_from_second.len()]
+    })
+}
+
+/// Generates lead from two given leads by cutting every bar at the beats.
+/// Random number of cut points (1..=3) is chosen among the beats
+/// of the time signature (except the first one), and the same points
+/// are used in all bars, so the bars that are equal in both parents
+/// stay equal in the child. Parts between the cut points
+/// are taken from the parents alternately, starting from the first one.
+/// Notes are assigned to the part by their start time
+
+#[inline]
+pub fn beat_crossover(
+    parent1: Vec<NoteData>,
+    parent2: Vec<NoteData>,
+    time_signature: TimeSignature,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
+    let beats = time_signature
+        .beat_positions()
+        .into_iter()
+        .skip(1)
+        .map(|beat| beat * STEP_TIME)
+        .collect::<Vec<_>>();
+
+    let co_points_num = rng.gen_range(1..=beats.len().clamp(1, 3));
+
+    let co_points = beats
+        .choose_multiple(rng, co_points_num)
+        .copied()
+        .collect::<Vec<_>>();
+
+    let bar_time = time_signature.bar_time();
+
+    merge_by_time(parent1, parent2, time_signature, |start| {
+        co_points
+            .iter()
+            .filter(|&&co_point| co_point <= start % bar_time)
+            .count()
+            % 2
+            == 1
+    })
+}
+
+/// Takes notes from the first parent, for which `from_second`
+/// is false, and notes from the second parent, for which it is true,
+/// judging by their absolute start time.
+/// Notes that sound over the beginning of the next note are shortened.
+/// Produced lead has delays recalculated from the absolute positions
+/// and starts relative to the beginning of the bar
+
+#[inline]
+fn merge_by_time(
+    parent1: Vec<NoteData>,
+    parent2: Vec<NoteData>,
+    time_signature: TimeSignature,
+    from_second: impl Fn(DeltaTime) -> bool,
+) -> Vec<NoteData> {
+    let mut notes = with_starts_from_delays(parent1)
+        .into_iter()
+        .filter(|note| !from_second(note.start()))
+        .chain(
+            with_starts_from_delays(parent2)
+                .into_iter()
+                .filter(|note| from_second(note.start())),
+        )
+        .sorted()
+        .collect::<Vec<_>>();
+
+    notes.dedup_by_key(|note| note.start());
+
+    let notes = notes
+        .iter()
+        .enumerate()
+        .map(|(i, note)| match notes.get(i + 1) {
+            Some(next) => {
+                note.clone_with_new_length(note.length().min(next.start() - note.start()))
+            }
+            None => *note,
+        })
+        .collect();
+
+    with_starts_in_bars(notes, time_signature)
+}
 
 /// Generates lead from two given leads by mixing them.
 /// Firstly, random number of crossover points (1..=4)
@@ -10,8 +217,8 @@ use rand::Rng;
 /// length equal to either first or second parents' lengths
 
 #[inline]
-pub fn crossover<T: Clone>(parent1: Vec<T>, parent2: Vec<T>) -> Vec<T> {
-    let co_points = generate_co_points(parent1.len(), parent2.len());
+pub fn crossover<T: Clone>(parent1: Vec<T>, parent2: Vec<T>, rng: &mut impl Rng) -> Vec<T> {
+    let co_points = generate_co_points(parent1.len(), parent2.len(), rng);
     perform_crossover(parent1, parent2, co_points)
 }
 
@@ -19,8 +226,7 @@ pub fn crossover<T: Clone>(parent1: Vec<T>, parent2: Vec<T>) -> Vec<T> {
 /// Smallest lead's length is taken as the generation bound
 
 #[inline]
-fn generate_co_points(parent1_len: usize, parent2_len: usize) -> Vec<usize> {
-    let mut rng = rand::thread_rng();
+fn generate_co_points(parent1_len: usize, parent2_len: usize, rng: &mut impl Rng) -> Vec<usize> {
     let max_co_point = std::cmp::min(parent1_len, parent2_len);
     let co_points = rng.gen_range(1..=4);
 
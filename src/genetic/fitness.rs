@@ -1,15 +1,36 @@
-use crate::{midi::bpm::BPM, notes::note_data::NoteData, WithNextIterable};
+use crate::{
+    midi::{
+        generator::{
+            cadence::{bar_endings, degree_pitch_class, CadencePlan},
+            with_starts_from_delays,
+        },
+        time_signature::{TimeSignature, STEP_TIME},
+    },
+    notes::{
+        note::Note,
+        note_data::{DeltaTime, NoteData},
+    },
+    rules::{
+        builtin::{LeapLimit, LegatoRunLimit, MinLegato, NoteRepetition, OddRhythm, PauseLimit},
+        MelodyRule, RuleSet,
+    },
+    WithNextIterable,
+};
 use itertools::Itertools;
+use rust_music_theory::note::PitchClass;
 
 /// Calculates fitness function of the given lead
 /// by comparing it with the ideal lead.
 ///
 /// Algorithm considers distances (pauses) between notes
 /// and gives 3/4 for the complete match only.
+/// Pauses of both leads are quantized to 1/16 steps,
+/// so they are compared on the same grid regardless of BPM
+/// (sample files are rescaled to the same resolution when parsed).
 /// Pitch coincidence is also taken into account and receives 1/4.
 /// Partial pitch coincidence is also considered.
 ///
-/// Additionally, algorithm uses next filters:
+/// Additionally, algorithm uses next filters ([RuleSet::fitness]):
 /// 1. Same note cannot repeat 3 times in a row
 /// 2. Distance between notes in semitones is less than 7
 /// 3. Parts with 7+ notes with zero delay are not allowed
@@ -18,99 +39,469 @@ use itertools::Itertools;
 /// Resulting fitness is in range 0..=1
 
 #[inline]
-pub fn fitness(bpm: impl BPM, lead: &Vec<NoteData>, ideal_lead: &Vec<NoteData>) -> f32 {
+pub fn fitness(lead: &Vec<NoteData>, ideal_lead: &Vec<NoteData>) -> f32 {
+    match RuleSet::fitness().is_satisfied(lead) {
+        true => similarity(lead, ideal_lead),
+        false => 0.0,
+    }
+}
+
+/// Similarity of the given lead with the ideal lead in range 0..=1,
+/// calculated as in [fitness], but without the filters.
+/// Empty lead has zero similarity, identical leads have similarity 1
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     genetic::fitness::similarity,
+///     midi::resolution::Resolution,
+///     notes::{note::Note, note_data::NoteData},
+/// };
+///
+/// let note = |midi: u8, start, length, delay| NoteData::new(Note::try_from(midi).unwrap(), 75, start, length, delay);
+/// let lead = vec![note(60, 0, 64, 0), note(62, 96, 64, 32), note(67, 160, 64, 0), note(64, 288, 64, 64)];
+/// assert!((similarity(&lead, &lead) - 1.0).abs() < 1e-6);
+///
+/// // Same lead played slightly off the grid in the file with 480 ticks per quarter
+/// let parsed = [note(60, 0, 240, 0), note(62, 362, 240, 122), note(67, 602, 240, 0), note(64, 1080, 240, 238)]
+///     .iter()
+///     .map(|note| note.clone_rescaled(Resolution::new(480).unwrap(), Resolution::GENERATION))
+///     .collect();
+///
+/// assert!((similarity(&lead, &parsed) - 1.0).abs() < 1e-6)
+/// ```
+
+#[inline]
+pub fn similarity(lead: &Vec<NoteData>, ideal_lead: &Vec<NoteData>) -> f32 {
+    if lead.is_empty() {
+        return 0.0;
+    }
+
     let note_match_ratio = 1.0 / lead.len() as f32;
     let note_dif_match_ratio = note_match_ratio / 26.0;
 
-    let bar_time = bpm.bar_time().as_millis() as u32;
-    let single_note_len = (bar_time as f64 / 16.0).round() as u32;
-
-    let fitness = lead.with_next().zip(ideal_lead.with_next()).fold(
-        note_match_ratio,
-        |fit_val, ((next, prev), (ideal_next, ideal_prev))| {
-            fit_val
-                + calc_fitness_for_next_note(
-                    next,
-                    prev,
-                    ideal_next,
-                    ideal_prev,
-                    note_match_ratio,
-                    note_dif_match_ratio,
-                    single_note_len,
-                )
-        },
-    );
-
-    if is_without_three_times_repetition(&lead)
-        && is_distance_between_notes_not_big(&lead)
-        && is_not_too_big_parts(&lead)
-        && is_not_many_delays(&lead)
-    {
-        fitness
-    } else {
-        0.0
+    lead.with_next()
+        .zip(ideal_lead.with_next())
+        .fold(
+            note_match_ratio,
+            |fit_val, ((next, prev), (ideal_next, ideal_prev))| {
+                fit_val
+                    + calc_fitness_for_next_note(
+                        next,
+                        prev,
+                        ideal_next,
+                        ideal_prev,
+                        note_match_ratio,
+                        note_dif_match_ratio,
+                    )
+            },
+        )
+        .clamp(0.0, 1.0)
+}
+
+/// Weights of the fitness components.
+/// Total fitness is the weighted mean of the components,
+/// components with zero weight are ignored
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FitnessWeights {
+    pub similarity: f32,
+    pub rules: f32,
+    pub contour: f32,
+    pub scale: f32,
+    pub cadence: f32,
+    pub density: f32,
+    pub range: f32,
+}
+
+impl Default for FitnessWeights {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            similarity: 4.0,
+            rules: 2.0,
+            contour: 1.0,
+            scale: 1.0,
+            cadence: 1.0,
+            density: 1.0,
+            range: 1.0,
+        }
+    }
+}
+
+/// How the lead is compared with the corpus of sample leads
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SimilarityMode {
+    /// Similarity with the most similar sample
+    #[default]
+    Nearest,
+
+    /// Mean similarity with all samples
+    Average,
+}
+
+/// Separate components of the lead's fitness, all in range 0..=1
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FitnessComponents {
+    /// Similarity with the samples' corpus (see [similarity])
+    pub similarity: f32,
+
+    /// Share of the satisfied rules of the fitness's rule set
+    pub rules: f32,
+
+    /// Smoothness of the melodic line: the smaller
+    /// intervals between notes are, the higher it is
+    pub contour: f32,
+
+    /// Share of the notes that lie on the scale
+    pub scale: f32,
+
+    /// Share of the phrase endings of the [CadencePlan]
+    /// that land on their planned degrees
+    pub cadence: f32,
+
+    /// Closeness of the number of notes per bar
+    /// to the mean one of the samples
+    pub density: f32,
+
+    /// Closeness of the lead's range in semitones
+    /// to the mean one of the samples
+    pub range: f32,
+}
+
+impl FitnessComponents {
+    /// Weighted mean of the components.
+    /// Negative weights are treated as zero ones
+
+    #[inline]
+    pub fn total(&self, weights: &FitnessWeights) -> f32 {
+        let weighted = [
+            (self.similarity, weights.similarity),
+            (self.rules, weights.rules),
+            (self.contour, weights.contour),
+            (self.scale, weights.scale),
+            (self.cadence, weights.cadence),
+            (self.density, weights.density),
+            (self.range, weights.range),
+        ]
+        .map(|(component, weight)| (component, weight.max(0.0)));
+
+        let weights_sum = weighted.iter().map(|(_, weight)| weight).sum::<f32>();
+
+        match weights_sum > 0.0 {
+            true => {
+                weighted
+                    .iter()
+                    .map(|(component, weight)| component * weight)
+                    .sum::<f32>()
+                    / weights_sum
+            }
+
+            false => 0.0,
+        }
+    }
+}
+
+/// Multi-objective fitness of the leads: similarity with
+/// the corpus of sample leads and the music-theory rules
+/// (see [FitnessComponents]), combined with the weights
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     genetic::fitness::{Fitness, FitnessWeights, SimilarityMode},
+///     midi::{generator::cadence::CadencePlan, time_signature::TimeSignature},
+///     notes::{note::Note, note_data::NoteData},
+///     rules::RuleSet,
+/// };
+/// use rust_music_theory::note::PitchClass;
+///
+/// // Bar per note, the 4-th bar ends on G and the 8-th one on C
+/// let note = |midi: u8| NoteData::new(Note::try_from(midi).unwrap(), 75, 0, 512, 0);
+/// let sample = [60, 62, 64, 67, 60, 62, 64, 60].map(note).to_vec();
+/// let scale_notes = [60, 62, 64, 65, 67, 69, 71].map(|midi| Note::try_from(midi).unwrap());
+///
+/// let fitness = Fitness::new(
+///     PitchClass::C,
+///     TimeSignature::COMMON,
+///     &scale_notes.to_vec(),
+///     CadencePlan::HALF_AND_AUTHENTIC,
+///     vec![sample.clone()],
+///     SimilarityMode::Nearest,
+///     RuleSet::fitness(),
+/// );
+///
+/// let components = fitness.components(&sample);
+/// assert!((components.similarity - 1.0).abs() < 1e-6);
+/// assert_eq!(components.scale, 1.0);
+/// assert_eq!(components.cadence, 1.0);
+/// assert_eq!(components.density, 1.0);
+///
+/// // Only the half cadence is in place
+/// let unresolved = [60, 62, 64, 67, 60, 62, 64, 62].map(note).to_vec();
+/// assert_eq!(fitness.components(&unresolved).cadence, 0.5);
+///
+/// let total = fitness.fitness(&sample, &FitnessWeights::default());
+/// assert!((0.0..=1.0).contains(&total))
+/// ```
+
+#[derive(Clone, Debug)]
+pub struct Fitness {
+    time_signature: TimeSignature,
+    scale_pitch_classes: Vec<u8>,
+    cadence_targets: Vec<(usize, u8)>,
+    ideal_leads: Vec<Vec<NoteData>>,
+    similarity_mode: SimilarityMode,
+    rules: RuleSet,
+    ideal_density: f32,
+    ideal_range: f32,
+}
+
+impl Fitness {
+    /// Prepares fitness for the leads of the given key,
+    /// time signature, scale and phrase endings, that are compared
+    /// with the given corpus of sample leads and checked with the rules
+
+    #[inline]
+    pub fn new(
+        key: PitchClass,
+        time_signature: TimeSignature,
+        scale_notes: &Vec<Note>,
+        cadences: CadencePlan,
+        ideal_leads: Vec<Vec<NoteData>>,
+        similarity_mode: SimilarityMode,
+        rules: RuleSet,
+    ) -> Self {
+        let ideal_density = mean(ideal_leads.iter().map(|lead| density(lead, time_signature)));
+
+        let ideal_range = mean(ideal_leads.iter().map(range));
+
+        Self {
+            time_signature,
+            scale_pitch_classes: scale_notes
+                .iter()
+                .map(|note| note.midi() % 12)
+                .unique()
+                .collect(),
+            cadence_targets: cadences
+                .targets()
+                .into_iter()
+                .filter_map(|(bar, degree)| {
+                    degree_pitch_class(key, scale_notes, degree).map(|pc| (bar, pc))
+                })
+                .collect(),
+            ideal_leads,
+            similarity_mode,
+            rules,
+            ideal_density,
+            ideal_range,
+        }
+    }
+
+    /// Total fitness of the lead in range 0..=1
+    /// (see [FitnessComponents::total])
+
+    #[inline]
+    pub fn fitness(&self, lead: &Vec<NoteData>, weights: &FitnessWeights) -> f32 {
+        self.components(lead).total(weights)
+    }
+
+    /// Calculates all components of the lead's fitness
+
+    #[inline]
+    pub fn components(&self, lead: &Vec<NoteData>) -> FitnessComponents {
+        FitnessComponents {
+            similarity: self.similarity(lead),
+            rules: self.rules.satisfied_share(lead),
+            contour: self.contour(lead),
+            scale: self.scale(lead),
+            cadence: self.cadence(lead),
+            density: closeness(density(lead, self.time_signature), self.ideal_density),
+            range: closeness(range(lead), self.ideal_range),
+        }
+    }
+
+    /// Index of the sample that is the most similar to the lead
+
+    #[inline]
+    pub fn nearest_sample(&self, lead: &Vec<NoteData>) -> Option<usize> {
+        self.similarities(lead)
+            .enumerate()
+            .max_by(|(_, x), (_, y)| x.total_cmp(y))
+            .map(|(i, _)| i)
+    }
+
+    /// Similarities of the lead with all samples
+
+    #[inline]
+    fn similarities<'a>(&'a self, lead: &'a Vec<NoteData>) -> impl Iterator<Item = f32> + 'a {
+        self.ideal_leads
+            .iter()
+            .map(|ideal_lead| similarity(lead, ideal_lead))
+    }
+
+    /// Similarity with the corpus according to the similarity mode
+
+    #[inline]
+    fn similarity(&self, lead: &Vec<NoteData>) -> f32 {
+        match self.similarity_mode {
+            SimilarityMode::Nearest => self
+                .similarities(lead)
+                .max_by(|x, y| x.total_cmp(y))
+                .unwrap_or(0.0),
+
+            SimilarityMode::Average => mean(self.similarities(lead)),
+        }
+    }
+
+    /// Mean of the intervals' scores, where unison scores 1
+    /// and octave (or larger interval) scores 0
+
+    #[inline]
+    fn contour(&self, lead: &Vec<NoteData>) -> f32 {
+        match lead.len() {
+            0 | 1 => 1.0,
+            _ => mean(lead.with_next().map(|(next, prev)| {
+                (1.0 - next.note().midi().abs_diff(prev.note().midi()) as f32 / 12.0).max(0.0)
+            })),
+        }
+    }
+
+    /// Share of the notes that lie on the scale
+
+    #[inline]
+    fn scale(&self, lead: &Vec<NoteData>) -> f32 {
+        mean(lead.iter().map(|note| {
+            match self
+                .scale_pitch_classes
+                .contains(&(note.note().midi() % 12))
+            {
+                true => 1.0,
+                false => 0.0,
+            }
+        }))
+    }
+
+    /// Share of the planned bars whose last notes are on the planned degrees.
+    /// Planned bars without notes score 0,
+    /// lead always scores 1 if there is nothing planned
+
+    #[inline]
+    fn cadence(&self, lead: &Vec<NoteData>) -> f32 {
+        if self.cadence_targets.is_empty() {
+            return 1.0;
+        }
+
+        let endings = bar_endings(lead, self.time_signature);
+
+        mean(self.cadence_targets.iter().map(|&(bar, pitch_class)| {
+            let ending = endings.iter().find(|(ending_bar, _)| *ending_bar == bar);
+
+            match ending {
+                Some(&(_, i)) if lead[i].note().midi() % 12 == pitch_class => 1.0,
+                _ => 0.0,
+            }
+        }))
+    }
+}
+
+/// Mean number of notes per bar
+
+#[inline]
+fn density(lead: &Vec<NoteData>, time_signature: TimeSignature) -> f32 {
+    let bar_time = time_signature.bar_time();
+
+    let end = with_starts_from_delays(lead.clone())
+        .last()
+        .map(|note| note.start() + note.length())
+        .unwrap_or(0);
+
+    lead.len() as f32 / end.div_ceil(bar_time).max(1) as f32
+}
+
+/// Distance between the highest and the lowest notes in semitones
+
+#[inline]
+fn range(lead: &Vec<NoteData>) -> f32 {
+    lead.iter()
+        .map(|note| note.note().midi())
+        .minmax()
+        .into_option()
+        .map(|(min, max)| (max - min) as f32)
+        .unwrap_or(0.0)
+}
+
+/// Closeness of the value to the target in range 0..=1
+
+#[inline]
+fn closeness(value: f32, target: f32) -> f32 {
+    match value.max(target) > 0.0 {
+        true => 1.0 - (value - target).abs() / value.max(target),
+        false => 1.0,
+    }
+}
+
+/// Mean of the values, zero if there are no values
+
+#[inline]
+fn mean(values: impl Iterator<Item = f32>) -> f32 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), x| (sum + x, count + 1));
+
+    match count {
+        0 => 0.0,
+        _ => sum / count as f32,
     }
 }
 
 /// Same note cannot repeat 3 times in a row
+/// (see [NoteRepetition])
 
 #[inline]
 pub fn is_without_three_times_repetition(lead: &Vec<NoteData>) -> bool {
-    lead.windows(3)
-        .map(|arr| {
-            arr.iter()
-                .map(|&x| x)
-                .collect_tuple::<(NoteData, NoteData, NoteData)>()
-                .unwrap()
-        })
-        .find(|(f, s, t)| f.note() == s.note() && s.note() == t.note())
-        .is_none()
+    NoteRepetition::default().violation(lead).is_none()
 }
 
 /// Distance between notes in semitones is less than 7
+/// (see [LeapLimit])
 
 #[inline]
 pub fn is_distance_between_notes_not_big(lead: &Vec<NoteData>) -> bool {
-    lead.with_next()
-        .all(|(next, prev)| next.note().midi().abs_diff(prev.note().midi()) < 7)
+    LeapLimit::default().violation(lead).is_none()
 }
 
 /// Parts with 7+ notes with zero delay are not allowed
+/// (see [LegatoRunLimit])
 
 #[inline]
 pub fn is_not_too_big_parts(lead: &Vec<NoteData>) -> bool {
-    lead.iter()
-        .skip(1)
-        .map(|x| x.delay())
-        .collect::<Vec<_>>()
-        .windows(6)
-        .find(|&arr| arr == [0, 0, 0, 0, 0, 0])
-        .is_none()
+    LegatoRunLimit::default().violation(lead).is_none()
 }
 
 /// Notes with start and the delay both equal to 1/16 of bar are not allowed.
 /// | |N|?|?| - such 1/4 of bars are not allowed
+/// (see [OddRhythm])
 
 #[inline]
 pub fn is_not_bad_rhythm_with_odd_notes(lead: &Vec<NoteData>) -> bool {
-    lead.iter()
-        .map(|x| (x.start() / 32 % 2, x.delay() / 32 % 2))
-        .find(|(start, delay)| *start == 1 && *delay == 1)
-        .is_none()
+    OddRhythm.violation(lead).is_none()
 }
 
 /// At least two notes go one after another without any pause
+/// (see [MinLegato])
 
 #[inline]
 pub fn is_at_least_one_zero_delay(lead: &Vec<NoteData>) -> bool {
-    lead.iter().map(|x| x.delay()).find(|x| *x == 0).is_some()
+    MinLegato::default().violation(lead).is_none()
 }
 
 /// Number of pauses is less than 4
+/// (see [PauseLimit])
 
 #[inline]
 pub fn is_not_many_delays(lead: &Vec<NoteData>) -> bool {
-    lead.iter().map(|x| x.delay()).filter(|d| *d != 0).count() < 4
+    PauseLimit::default().violation(lead).is_none()
 }
 
 /// Algorithm considers distances (pauses) between notes
@@ -126,10 +517,9 @@ fn calc_fitness_for_next_note(
     ideal_prev: &NoteData,
     note_match_ratio: f32,
     note_dif_match_ratio: f32,
-    single_note_len: u32,
 ) -> f32 {
-    let cur_delay = next.delay() / single_note_len;
-    let next_delay = ideal_next.delay() / 32;
+    let cur_delay = to_steps(next.delay());
+    let next_delay = to_steps(ideal_next.delay());
     let delay_dif_match = if cur_delay == next_delay {
         note_match_ratio * 3.0 / 4.0
     } else {
@@ -144,3 +534,10 @@ fn calc_fitness_for_next_note(
 
     delay_dif_match + pitch_dif_match
 }
+
+/// Number of 1/16 steps in the time, rounded to the nearest step
+
+#[inline]
+fn to_steps(time: DeltaTime) -> u32 {
+    (time + STEP_TIME / 2) / STEP_TIME
+}
@@ -0,0 +1,95 @@
+/// Settings of the single sub-population of the island model.
+/// Missing settings are taken from the [super::config::GeneticConfig]
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Island {
+    /// Probability in range 0..=1 of every note's pitch to be mutated
+    pub mutation_rate: Option<f32>,
+
+    /// Index of the sample file (in the sorted list of the readable
+    /// files of the samples folder) that is the only target of the island.
+    /// Index has to be less than the number of samples.
+    /// Island is compared with all samples if it is not specified
+    pub sample: Option<usize>,
+}
+
+/// Island model of the genetic algorithm: several sub-populations
+/// evolve independently and periodically exchange their best leads.
+/// Islands are connected in a ring: the fittest leads of every island
+/// replace the least fit leads of the next one.
+/// Population size of the config is split evenly between islands
+///
+/// # Example
+/// ```
+/// use music_generator::genetic::{config::GeneticConfig, island::IslandModel};
+///
+/// let config = GeneticConfig {
+///     islands: Some(IslandModel::with_mutation_rates(&[0.05, 0.1, 0.2, 0.4])),
+///     ..GeneticConfig::default()
+/// };
+///
+/// let islands = config.islands.unwrap();
+/// assert_eq!(islands.islands.len(), 4);
+/// assert_eq!(islands.islands[3].mutation_rate, Some(0.4))
+/// ```
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IslandModel {
+    pub islands: Vec<Island>,
+
+    /// Number of generations between migrations
+    pub migration_interval: usize,
+
+    /// Number of the fittest leads that every island sends to the next one
+    pub migrants: usize,
+}
+
+impl IslandModel {
+    /// Default number of generations between migrations
+    pub const MIGRATION_INTERVAL: usize = 10;
+
+    /// Default number of leads that every island sends
+    pub const MIGRANTS: usize = 5;
+
+    /// Constructs model with the given islands,
+    /// default migration interval and number of migrants
+
+    #[inline]
+    pub fn new(islands: Vec<Island>) -> Self {
+        Self {
+            islands,
+            migration_interval: Self::MIGRATION_INTERVAL,
+            migrants: Self::MIGRANTS,
+        }
+    }
+
+    /// Islands that differ by the mutation rate only
+
+    #[inline]
+    pub fn with_mutation_rates(mutation_rates: &[f32]) -> Self {
+        Self::new(
+            mutation_rates
+                .iter()
+                .map(|&mutation_rate| Island {
+                    mutation_rate: Some(mutation_rate),
+                    sample: None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Given number of islands, every one targets its own sample.
+    /// There have to be at least as many samples as islands
+
+    #[inline]
+    pub fn with_samples(islands: usize) -> Self {
+        Self::new(
+            (0..islands)
+                .map(|sample| Island {
+                    mutation_rate: None,
+                    sample: Some(sample),
+                })
+                .collect(),
+        )
+    }
+}
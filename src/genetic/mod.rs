@@ -1,197 +1,520 @@
 use crate::{
+    error::{Error, Result},
     genetic::{
-        crossover::crossover, fitness::fitness, mutation::mutate,
-        selection::select_from_population_with_roulette,
+        config::GeneticConfig,
+        fitness::Fitness,
+        island::Island,
+        mutation::{mutate_with_pipeline, Mutation},
+        parallel::{par_generate, par_map, thread_pool},
+        selection::Selection,
+        stats::{GenerationStats, GeneticResult, Termination},
     },
     melody_type::SynthwaveMelodyType,
-    midi::{bpm::BPM, generator::random_from_vec, parser::midi_file_manager::extract_notes},
+    midi::{parser::midi_file_manager::extract_notes, time_signature::TimeSignature},
     notes::{note::Note, note_data::NoteData},
+    rules::builtin::PhraseEndings,
 };
 
-use rand::Rng;
+use itertools::Itertools;
+use rand::{seq::SliceRandom, Rng};
+use rayon::ThreadPool;
 use rust_music_theory::note::PitchClass;
+use std::time::Instant;
 
-mod crossover;
+pub mod config;
+pub mod crossover;
 pub mod fitness;
+pub mod island;
 pub mod mutation;
-mod selection;
-
-const MAX_GENERATIONS: usize = 100;
+pub mod parallel;
+pub mod selection;
+pub mod stats;
 
 type LeadPopulation = Vec<Vec<NoteData>>;
 
 /// Generates synthwave-style 8 bar lead melody
-/// by the given key, BPM, scale and melody type
-/// from the .mid files of 'genetic_samples' folder
-/// with the given fitness and mutation rate.
-/// Generates melodies until fitness and mutation rate
-/// reaches desired bound
+/// by the given key, scale and melody type
+/// that is compared with all .mid files of the samples folder
+/// and the music-theory rules (see [fitness::Fitness])
+/// with the given hyperparameters.
+/// Several sub-populations are evolved with the island model
+/// if it is specified in the config. Islands select leads
+/// with their own fitness, while the best lead of the run is chosen,
+/// checked for the desired fitness and reported with the fitness
+/// against all samples.
+/// Generates populations until one of the termination
+/// conditions of the config is met and returns the fittest
+/// lead among all generations with the statistics of the run.
+/// Progress of every generation is reported
+/// with the `on_generation` callback.
+/// All random decisions are taken with the given RNG,
+/// so the same seed produces the same lead.
+/// Sample leads are written in 4/4,
+/// so produced leads are also in 4/4.
+/// Returns [Error::NoSamples] if there are no
+/// readable sample files in the samples folder and
+/// [Error::InvalidIslandSample] if the island targets
+/// the sample that is not there
 
 #[inline]
 pub async fn generate_lead_with_genetic_algorithm(
     key: PitchClass,
-    bpm: impl BPM,
     scale_notes: &Vec<Note>,
     melody_type: SynthwaveMelodyType,
-    desired_fitness: f32,
-    mutation_rate: f32,
-) -> Vec<NoteData> {
-    loop {
-        let generated = try_generate_lead_with_genetic_algorithm(
+    config: &GeneticConfig,
+    rng: &mut impl Rng,
+    mut on_generation: impl FnMut(GenerationStats),
+) -> Result<GeneticResult> {
+    let start = Instant::now();
+    let (leads, skipped_samples) = extract_notes(&config.samples_path).await?;
+    let (samples, ideal_leads): (Vec<_>, Vec<_>) = leads.into_iter().unzip();
+
+    if samples.is_empty() {
+        return Err(Error::NoSamples);
+    }
+
+    let pool = thread_pool(config.threads).map_err(|err| Error::ThreadPool(err.to_string()))?;
+
+    let cadences = melody_type.cadences();
+    let rules = config.rules.clone().with_rule(PhraseEndings::new(
+        cadences,
+        key,
+        scale_notes,
+        TimeSignature::COMMON,
+    ));
+
+    let corpus_fitness = Fitness::new(
+        key,
+        TimeSignature::COMMON,
+        scale_notes,
+        cadences,
+        ideal_leads.clone(),
+        config.similarity,
+        rules.clone(),
+    );
+
+    let islands = match &config.islands {
+        Some(model) if !model.islands.is_empty() => model.islands.clone(),
+        _ => vec![Island::default()],
+    };
+
+    let island_size = (config.population_size / islands.len()).max(1);
+
+    let mut populations = islands
+        .iter()
+        .map(|island| {
+            let island_config = GeneticConfig {
+                mutation_rate: island.mutation_rate.unwrap_or(config.mutation_rate),
+                population_size: island_size,
+                ..config.clone()
+            };
+
+            let fitness = match island.sample {
+                None => corpus_fitness.clone(),
+                Some(sample) => {
+                    let ideal_lead = ideal_leads.get(sample).ok_or(Error::InvalidIslandSample {
+                        sample,
+                        samples: ideal_leads.len(),
+                    })?;
+
+                    Fitness::new(
+                        key,
+                        TimeSignature::COMMON,
+                        scale_notes,
+                        cadences,
+                        vec![ideal_lead.clone()],
+                        config.similarity,
+                        rules.clone(),
+                    )
+                }
+            };
+
+            Population::new(
+                key,
+                scale_notes,
+                melody_type,
+                island_config,
+                fitness,
+                &pool,
+                rng,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (mut best_lead, mut best_fitness) = fittest_of_islands(&populations, &corpus_fitness);
+
+    let mut history = vec![generation_stats(0, &populations)];
+    let mut stagnation = 0;
+    on_generation(history[0]);
+
+    let termination = loop {
+        if let Some(termination) = termination(config, best_fitness, &history, stagnation, start) {
+            break termination;
+        }
+
+        populations
+            .iter_mut()
+            .for_each(|population| population.evolve(scale_notes, melody_type, &pool, rng));
+
+        if let Some(model) = &config.islands {
+            if history.len() % model.migration_interval.max(1) == 0 {
+                migrate(&mut populations, model.migrants);
+            }
+        }
+
+        let stats = generation_stats(history.len(), &populations);
+        on_generation(stats);
+        history.push(stats);
+
+        let (lead, fitness) = fittest_of_islands(&populations, &corpus_fitness);
+
+        if fitness > best_fitness {
+            (best_lead, best_fitness) = (lead, fitness);
+            stagnation = 0;
+        } else {
+            stagnation += 1;
+        }
+    };
+
+    let sample = corpus_fitness
+        .nearest_sample(&best_lead)
+        .map(|i| samples[i].clone())
+        .unwrap_or_default();
+
+    Ok(GeneticResult {
+        components: corpus_fitness.components(&best_lead),
+        lead: best_lead,
+        fitness: best_fitness,
+        sample,
+        samples,
+        skipped_samples,
+        history,
+        termination,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Sub-population of the genetic algorithm (the island)
+/// with its own settings and fitness
+
+struct Population {
+    leads: LeadPopulation,
+    fitness_values: Vec<f32>,
+    fitness: Fitness,
+    config: GeneticConfig,
+}
+
+impl Population {
+    /// Generates and evaluates the initial population
+    /// of the size specified in the config on the threads of the pool
+
+    #[inline]
+    fn new(
+        key: PitchClass,
+        scale_notes: &Vec<Note>,
+        melody_type: SynthwaveMelodyType,
+        config: GeneticConfig,
+        fitness: Fitness,
+        pool: &ThreadPool,
+        rng: &mut impl Rng,
+    ) -> Result<Self> {
+        let leads = initial_population(
             key,
-            bpm,
             scale_notes,
             melody_type,
-            desired_fitness,
-            mutation_rate,
-        )
-        .await;
+            config.population_size.max(1),
+            pool,
+            rng,
+        )?;
 
-        if let Some(success) = generated {
-            break success;
-        }
+        let fitness_values = next_fitness(&fitness, &leads, &config, pool);
+
+        Ok(Self {
+            leads,
+            fitness_values,
+            fitness,
+            config,
+        })
+    }
+
+    /// Replaces population with the next generation.
+    /// The fittest leads are moved to the next generation as is,
+    /// the rest are children of the selected leads.
+    /// Children are generated and evaluated on the threads of the pool
+
+    #[inline]
+    fn evolve(
+        &mut self,
+        scale_notes: &Vec<Note>,
+        melody_type: SynthwaveMelodyType,
+        pool: &ThreadPool,
+        rng: &mut impl Rng,
+    ) {
+        let population_size = self.config.population_size.max(1);
+        let elitism = self.config.elitism.min(population_size);
+
+        let elite = elite(&self.leads, &self.fitness_values, elitism);
+        let selected = self
+            .config
+            .selection
+            .select(&self.leads, &self.fitness_values, rng);
+
+        self.leads = elite
+            .into_iter()
+            .chain(next_population(
+                &selected,
+                scale_notes,
+                melody_type,
+                &self.config,
+                population_size - elitism,
+                pool,
+                rng,
+            ))
+            .collect();
+
+        self.fitness_values = next_fitness(&self.fitness, &self.leads, &self.config, pool);
+    }
+
+    /// Replaces the least fit leads with the given ones,
+    /// which are evaluated with the fitness of the population
+
+    #[inline]
+    fn replace_least_fit(&mut self, leads: LeadPopulation) {
+        let least_fit = (0..self.leads.len())
+            .sorted_by(|&x, &y| self.fitness_values[x].total_cmp(&self.fitness_values[y]))
+            .collect::<Vec<_>>();
+
+        least_fit.into_iter().zip(leads).for_each(|(i, lead)| {
+            self.fitness_values[i] = self.fitness.fitness(&lead, &self.config.fitness_weights);
+            self.leads[i] = lead;
+        });
     }
 }
 
-/// Attempts to generate synthwave-style 8 bar lead melody
-/// with the given key, BPM, scale and melody type
-/// from the .mid files of 'genetic_samples' folder
-/// with the given fitness and mutation rate.
+/// Moves copies of the fittest leads of every island
+/// to the next island in the ring
 
 #[inline]
-async fn try_generate_lead_with_genetic_algorithm(
-    key: PitchClass,
-    bpm: impl BPM,
-    scale_notes: &Vec<Note>,
-    melody_type: SynthwaveMelodyType,
-    desired_fitness: f32,
-    mutation_rate: f32,
-) -> Option<Vec<NoteData>> {
-    let mut ideal_leads = extract_notes().await.ok()?;
-    let (path, ideal_lead) = random_from_vec(&mut ideal_leads)?;
-
-    let population = initial_population(key, scale_notes, melody_type);
-    let fitness_values = next_fitness(bpm, &population, &ideal_lead);
-    let max_fit = max_fitness(&fitness_values);
-    let population_size = population.len();
-
-    println!("Chosen lead: {:?}", path);
-    println!("IDEAL: {:?}", ideal_lead);
-
-    (0..=MAX_GENERATIONS)
-        .scan(
-            (population, fitness_values, max_fit),
-            |(population, fitness_values, max_fit), _| {
-                let mut selected =
-                    select_from_population_with_roulette(&population, fitness_values.clone());
-
-                *population =
-                    next_population(&mut selected, scale_notes, mutation_rate, population_size);
-
-                *fitness_values = next_fitness(bpm, population, &ideal_lead);
-                *max_fit = max_fitness(fitness_values);
-
-                Some((population.clone(), fitness_values.clone(), *max_fit))
-            },
-        )
-        .find(|(_, _, max_fitness)| *max_fitness >= desired_fitness)
-        .map(|(population, fitness_values, max_fitness)| {
-            let first_ok_fitness_ind = fitness_values
-                .into_iter()
-                .position(|x| x == max_fitness)
-                .unwrap();
-
-            population
-                .into_iter()
-                .skip(first_ok_fitness_ind)
-                .next()
-                .unwrap()
+fn migrate(populations: &mut Vec<Population>, migrants: usize) {
+    if populations.len() < 2 {
+        return;
+    }
+
+    let emigrants = populations
+        .iter()
+        .map(|population| elite(&population.leads, &population.fitness_values, migrants))
+        .collect::<Vec<_>>();
+
+    let islands = populations.len();
+
+    emigrants
+        .into_iter()
+        .enumerate()
+        .for_each(|(i, leads)| populations[(i + 1) % islands].replace_least_fit(leads));
+}
+
+/// Statistics of all islands' leads together
+
+#[inline]
+fn generation_stats(generation: usize, populations: &Vec<Population>) -> GenerationStats {
+    let fitness_values = populations
+        .iter()
+        .flat_map(|population| population.fitness_values.iter().copied())
+        .collect();
+
+    GenerationStats::new(generation, &fitness_values)
+}
+
+/// The fittest lead among all islands with its fitness.
+/// Islands select their fittest leads with their own fitness,
+/// which may target a single sample, so these leads are
+/// compared by the given fitness with the whole corpus
+
+#[inline]
+fn fittest_of_islands(
+    populations: &Vec<Population>,
+    corpus_fitness: &Fitness,
+) -> (Vec<NoteData>, f32) {
+    populations
+        .iter()
+        .map(|population| {
+            let (lead, _) = fittest(&population.leads, &population.fitness_values);
+            let fitness = corpus_fitness.fitness(&lead, &population.config.fitness_weights);
+            (lead, fitness)
         })
+        .max_by(|(_, x), (_, y)| x.total_cmp(y))
+        .unwrap_or_default()
+}
+
+/// Checks termination conditions of the config
+/// in order: desired fitness, maximum generations,
+/// time budget and stagnation limit
+
+#[inline]
+fn termination(
+    config: &GeneticConfig,
+    best_fitness: f32,
+    history: &Vec<GenerationStats>,
+    stagnation: usize,
+    start: Instant,
+) -> Option<Termination> {
+    if best_fitness >= config.desired_fitness {
+        Some(Termination::DesiredFitness)
+    } else if history.len() > config.max_generations {
+        Some(Termination::MaxGenerations)
+    } else if config
+        .time_budget
+        .is_some_and(|budget| start.elapsed() >= budget)
+    {
+        Some(Termination::TimeBudget)
+    } else if config
+        .stagnation_limit
+        .is_some_and(|limit| stagnation >= limit)
+    {
+        Some(Termination::Stagnation)
+    } else {
+        None
+    }
 }
 
-/// Generates 1000 synthwave-styled 8 bar leads
-/// by the given scale and the melody type
+/// Generates the given number of synthwave-styled 8 bar leads
+/// by the given scale and the melody type.
+/// Leads are generated on the threads of the pool,
+/// every lead with its own seeded RNG
 
 #[inline]
 fn initial_population(
     key: PitchClass,
     scale_notes: &Vec<Note>,
     melody_type: SynthwaveMelodyType,
-) -> LeadPopulation {
-    (0..)
-        .map(|_| melody_type.generate_synthwave_melody(key, scale_notes))
-        .take(1000)
-        .collect::<Vec<_>>()
+    population_size: usize,
+    pool: &ThreadPool,
+    rng: &mut impl Rng,
+) -> Result<LeadPopulation> {
+    par_generate(pool, population_size, rng, |rng| {
+        melody_type.generate_synthwave_melody(key, scale_notes, TimeSignature::COMMON, rng)
+    })
+    .into_iter()
+    .collect()
 }
 
 /// Generates next population by crossovering random pairs
-/// of leads that were previously selected and mutating the result.
-/// With probability of 0.25, one parent can also be added to the next population
+/// of leads of the melody type that were previously selected and mutating the result.
+/// With the parent survival probability of the config,
+/// one parent can also be added to the next population.
+/// Children are generated on the threads of the pool,
+/// every child with its own seeded RNG
 
 #[inline]
 fn next_population(
-    selected: &mut LeadPopulation,
+    selected: &LeadPopulation,
     scale_notes: &Vec<Note>,
-    mutation_rate: f32,
+    melody_type: SynthwaveMelodyType,
+    config: &GeneticConfig,
     population_size: usize,
+    pool: &ThreadPool,
+    rng: &mut impl Rng,
 ) -> LeadPopulation {
-    (0..)
-        .map(|_| next_child_with_mb_parent(selected, scale_notes, mutation_rate))
-        .filter_map(|population| population)
-        .flatten()
-        .take(population_size)
-        .collect::<Vec<_>>()
+    let mutation_pipeline = config.mutation_pipeline();
+
+    par_generate(pool, population_size, rng, |rng| {
+        next_child_with_mb_parent(
+            selected,
+            scale_notes,
+            melody_type,
+            config,
+            &mutation_pipeline,
+            rng,
+        )
+    })
+    .into_iter()
+    .flatten()
+    .flatten()
+    .take(population_size)
+    .collect::<Vec<_>>()
 }
 
 /// Generates entity for the next population by crossovering random pairs
 /// of leads that were previously selected and mutating the result.
-/// With probability of 0.25, one parent can also be added.
+/// With the parent survival probability, one parent can also be added.
 /// In total, produces either 0, 1 or 2 leads
 
 #[inline]
 fn next_child_with_mb_parent(
-    selected: &mut LeadPopulation,
+    selected: &LeadPopulation,
     scale_notes: &Vec<Note>,
-    mutation_rate: f32,
+    melody_type: SynthwaveMelodyType,
+    config: &GeneticConfig,
+    mutation_pipeline: &Vec<Mutation>,
+    rng: &mut impl Rng,
 ) -> Option<LeadPopulation> {
     let mut population = Vec::with_capacity(2);
-    let parent1 = random_from_vec(selected)?;
-    let parent2 = random_from_vec(selected)?;
+    let parent1 = selected.choose(rng)?.clone();
+    let parent2 = selected.choose(rng)?.clone();
 
-    let child = crossover(parent1.clone(), parent2.clone());
-    let child = mutate(child, scale_notes, mutation_rate);
+    let child = config.crossover.crossover(
+        parent1.clone(),
+        parent2.clone(),
+        melody_type,
+        TimeSignature::COMMON,
+        rng,
+    );
+    let child = mutate_with_pipeline(
+        child,
+        mutation_pipeline,
+        scale_notes,
+        TimeSignature::COMMON,
+        rng,
+    );
     population.push(child);
 
-    if rand::thread_rng().gen_bool(0.25) {
-        population.push(random_from_vec(&mut vec![parent1, parent2]).unwrap())
+    if rng.gen_bool(config.parent_survival_probability.clamp(0.0, 1.0)) {
+        population.push(match rng.gen_bool(0.5) {
+            true => parent1,
+            false => parent2,
+        })
     }
 
     Some(population)
 }
 
 /// Calculates fitness values for the given population
-/// by comparing it with the ideal lead
+/// with the weights of the fitness components on the threads of the pool
 
 #[inline]
 fn next_fitness(
-    bpm: impl BPM,
+    fitness: &Fitness,
     population: &LeadPopulation,
-    ideal_lead: &Vec<NoteData>,
+    config: &GeneticConfig,
+    pool: &ThreadPool,
 ) -> Vec<f32> {
+    par_map(pool, population, |lead| {
+        fitness.fitness(lead, &config.fitness_weights)
+    })
+}
+
+/// The fittest leads of the population, starting from the best one
+
+#[inline]
+fn elite(population: &LeadPopulation, fitness_values: &Vec<f32>, count: usize) -> LeadPopulation {
     population
         .iter()
-        .map(|lead| fitness(bpm, lead, &ideal_lead))
-        .collect::<Vec<_>>()
+        .zip(fitness_values)
+        .sorted_by(|(_, x), (_, y)| y.total_cmp(x))
+        .take(count)
+        .map(|(lead, _)| lead.clone())
+        .collect()
 }
 
-/// Calculates maximum fitness value from the given fitness list.
-/// If list is empty, produces 0
+/// The fittest lead of the population with its fitness.
+/// If population is empty, produces empty lead with zero fitness
 
 #[inline]
-fn max_fitness(fitness_values: &Vec<f32>) -> f32 {
-    fitness_values
+fn fittest(population: &LeadPopulation, fitness_values: &Vec<f32>) -> (Vec<NoteData>, f32) {
+    population
         .iter()
-        .map(|&x| x)
-        .max_by(|x, y| x.partial_cmp(y).unwrap())
-        .unwrap_or(0.0)
+        .zip(fitness_values)
+        .max_by(|(_, x), (_, y)| x.total_cmp(y))
+        .map(|(lead, &fitness)| (lead.clone(), fitness))
+        .unwrap_or_default()
 }
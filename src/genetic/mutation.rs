@@ -1,25 +1,392 @@
 use crate::{
-    midi::generator::generator::randomize_note,
-    notes::{note::Note, note_data::NoteData},
+    midi::{
+        generator::{
+            generator::{randomize_lead, randomize_note},
+            with_starts_from_delays, with_starts_in_bars,
+        },
+        time_signature::{TimeSignature, STEP_TIME},
+    },
+    notes::{
+        note::Note,
+        note_data::{DeltaTime, NoteData},
+    },
 };
 
+use itertools::Itertools;
 use rand::Rng;
 
+/// Maximum change of the velocity with [MutationOperator::Velocity]
+const VELOCITY_SPREAD: i16 = 16;
+
 /// Performs the lead mutation by randomizing notes' pitches.
 /// Every note may be randomized with probability equal to mutation rate.
 /// Generated notes' frequencies are belong to the given scale
 
 #[inline]
-pub fn mutate(lead: Vec<NoteData>, scale_notes: &Vec<Note>, mutation_rate: f32) -> Vec<NoteData> {
-    let mut rng = rand::thread_rng();
-
+pub fn mutate(
+    lead: Vec<NoteData>,
+    scale_notes: &Vec<Note>,
+    mutation_rate: f32,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
     lead.into_iter()
         .map(|note| {
             if rng.gen_bool(mutation_rate as f64) {
-                randomize_note(note, scale_notes)
+                randomize_note(note, scale_notes, rng)
             } else {
                 note
             }
         })
         .collect()
 }
+
+/// Mutation operators of the genetic algorithm.
+/// All operators except [MutationOperator::Transpose]
+/// are applied to every note with the rate of the mutation.
+/// Notes never cross the bar line and never overlap
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MutationOperator {
+    /// Pitch is changed to the close note of the scale
+    Pitch,
+
+    /// Note of at least two 1/16 steps is split into two notes of the same pitch
+    Split,
+
+    /// Note is merged with the next note of the bar
+    /// if there is no rest between them
+    Merge,
+
+    /// Note is moved by a 1/16 step earlier or later
+    Shift,
+
+    /// Note is lengthened or shortened by a 1/16 step
+    Length,
+
+    /// Velocity is changed by up to 16
+    Velocity,
+
+    /// Rest after the note is filled by the note.
+    /// If there is no rest, note itself is replaced with the rest
+    /// (unless it is the only note of the bar)
+    Rest,
+
+    /// All notes of the bar are moved up or down on the scale.
+    /// Applied to every bar with the rate of the mutation
+    Transpose,
+}
+
+/// Mutation operator with the probability in range 0..=1 to be applied
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mutation {
+    pub operator: MutationOperator,
+    pub rate: f32,
+}
+
+impl Mutation {
+    #[inline]
+    pub fn new(operator: MutationOperator, rate: f32) -> Self {
+        Self { operator, rate }
+    }
+
+    /// Applies operator to the notes with absolute starts
+    /// (see [with_starts_from_delays])
+
+    #[inline]
+    fn apply(
+        &self,
+        notes: Vec<NoteData>,
+        scale_notes: &Vec<Note>,
+        time_signature: TimeSignature,
+        rng: &mut impl Rng,
+    ) -> Vec<NoteData> {
+        let rate = self.rate.clamp(0.0, 1.0) as f64;
+        let bar_time = time_signature.bar_time();
+
+        match self.operator {
+            MutationOperator::Pitch => mutate(notes, scale_notes, rate as f32, rng),
+            MutationOperator::Split => split_notes(notes, rate, rng),
+            MutationOperator::Merge => merge_notes(notes, rate, bar_time, rng),
+            MutationOperator::Shift => shift_notes(notes, rate, bar_time, rng),
+            MutationOperator::Length => change_lengths(notes, rate, bar_time, rng),
+            MutationOperator::Velocity => change_velocities(notes, rate, rng),
+            MutationOperator::Rest => change_rests(notes, rate, bar_time, rng),
+            MutationOperator::Transpose => transpose_bars(notes, scale_notes, rate, bar_time, rng),
+        }
+    }
+}
+
+/// Applies mutations of the pipeline to the lead one after another.
+/// Notes' positions are calculated from delays and lengths,
+/// mutated lead has starts and delays derived from them again
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     genetic::mutation::{mutate_with_pipeline, Mutation, MutationOperator},
+///     midi::time_signature::TimeSignature,
+///     notes::{note::Note, note_data::NoteData},
+/// };
+///
+/// let note = Note::try_from(60).unwrap();
+/// let lead = vec![
+///     NoteData::new(note, 75, 0, 128, 0),
+///     NoteData::new(note, 75, 256, 128, 128),
+/// ];
+///
+/// let pipeline = vec![Mutation::new(MutationOperator::Split, 1.0)];
+/// let mut rng = rand::thread_rng();
+/// let mutated = mutate_with_pipeline(lead, &pipeline, &vec![note], TimeSignature::COMMON, &mut rng);
+///
+/// assert_eq!(mutated.len(), 4);
+/// assert_eq!(mutated.iter().map(|n| n.length()).sum::<u32>(), 256);
+/// assert_eq!(mutated[3].start() + mutated[3].length(), 384)
+/// ```
+
+#[inline]
+pub fn mutate_with_pipeline(
+    lead: Vec<NoteData>,
+    pipeline: &Vec<Mutation>,
+    scale_notes: &Vec<Note>,
+    time_signature: TimeSignature,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
+    let notes = pipeline
+        .iter()
+        .fold(with_starts_from_delays(lead), |notes, mutation| {
+            mutation.apply(notes, scale_notes, time_signature, rng)
+        });
+
+    with_starts_in_bars(notes, time_signature)
+}
+
+/// Splits notes of at least two steps at the random step
+
+#[inline]
+fn split_notes(notes: Vec<NoteData>, rate: f64, rng: &mut impl Rng) -> Vec<NoteData> {
+    notes
+        .into_iter()
+        .flat_map(|note| {
+            let steps = note.length() / STEP_TIME;
+
+            match steps >= 2 && rng.gen_bool(rate) {
+                true => {
+                    let first_len = rng.gen_range(1..steps) * STEP_TIME;
+
+                    vec![
+                        note.clone_with_new_length(first_len),
+                        note.clone_with_new_start(note.start() + first_len)
+                            .clone_with_new_length(note.length() - first_len),
+                    ]
+                }
+
+                false => vec![note],
+            }
+        })
+        .collect()
+}
+
+/// Merges notes with the previous ones of the same bar
+/// if there is no rest between them.
+/// Merged note has the pitch of the previous one
+
+#[inline]
+fn merge_notes(
+    notes: Vec<NoteData>,
+    rate: f64,
+    bar_time: DeltaTime,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
+    notes
+        .into_iter()
+        .fold(Vec::new(), |mut merged: Vec<NoteData>, note| {
+            match merged.last_mut() {
+                Some(prev)
+                    if end(prev) == note.start()
+                        && prev.start() / bar_time == note.start() / bar_time
+                        && rng.gen_bool(rate) =>
+                {
+                    *prev = prev.clone_with_new_length(prev.length() + note.length())
+                }
+
+                _ => merged.push(note),
+            }
+
+            merged
+        })
+}
+
+/// Moves notes by a step if they still fit
+/// between the neighbours and inside the bar
+
+#[inline]
+fn shift_notes(
+    mut notes: Vec<NoteData>,
+    rate: f64,
+    bar_time: DeltaTime,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
+    for i in 0..notes.len() {
+        if !rng.gen_bool(rate) {
+            continue;
+        }
+
+        let note = notes[i];
+        let (min_start, max_end) = free_space(&notes, i, bar_time);
+
+        let start = match rng.gen_bool(0.5) {
+            true => note.start().checked_add(STEP_TIME),
+            false => note.start().checked_sub(STEP_TIME),
+        };
+
+        if let Some(start) = start.filter(|&s| s >= min_start && s + note.length() <= max_end) {
+            notes[i] = note.clone_with_new_start(start);
+        }
+    }
+
+    notes
+}
+
+/// Lengthens or shortens notes by a step
+/// if they still fit before the next note and inside the bar
+
+#[inline]
+fn change_lengths(
+    mut notes: Vec<NoteData>,
+    rate: f64,
+    bar_time: DeltaTime,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
+    for i in 0..notes.len() {
+        if !rng.gen_bool(rate) {
+            continue;
+        }
+
+        let note = notes[i];
+        let (_, max_end) = free_space(&notes, i, bar_time);
+
+        let length = match rng.gen_bool(0.5) {
+            true => note.length() + STEP_TIME,
+            false => note.length().saturating_sub(STEP_TIME),
+        };
+
+        if length >= STEP_TIME && note.start() + length <= max_end {
+            notes[i] = note.clone_with_new_length(length);
+        }
+    }
+
+    notes
+}
+
+/// Changes velocities by up to [VELOCITY_SPREAD]
+
+#[inline]
+fn change_velocities(notes: Vec<NoteData>, rate: f64, rng: &mut impl Rng) -> Vec<NoteData> {
+    notes
+        .into_iter()
+        .map(|note| match rng.gen_bool(rate) {
+            true => {
+                let spread = rng.gen_range(-VELOCITY_SPREAD..=VELOCITY_SPREAD);
+                let velocity = (note.velocity() as i16 + spread).clamp(1, 127);
+                note.clone_with_velocity(velocity as u8)
+            }
+
+            false => note,
+        })
+        .collect()
+}
+
+/// Fills rests after the notes with the notes themselves,
+/// or replaces notes with rests if there are no rests after them
+
+#[inline]
+fn change_rests(
+    mut notes: Vec<NoteData>,
+    rate: f64,
+    bar_time: DeltaTime,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
+    let mut i = 0;
+
+    while i < notes.len() {
+        if rng.gen_bool(rate) {
+            let note = notes[i];
+            let (_, max_end) = free_space(&notes, i, bar_time);
+            let bar = note.start() / bar_time;
+
+            let is_alone = [i.checked_sub(1), Some(i + 1)]
+                .into_iter()
+                .flatten()
+                .filter_map(|j| notes.get(j))
+                .all(|other| other.start() / bar_time != bar);
+
+            if end(&note) < max_end {
+                notes[i] = note.clone_with_new_length(max_end - note.start());
+            } else if !is_alone {
+                notes.remove(i);
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    notes
+}
+
+/// Moves all notes of the bar up or down on the scale
+
+#[inline]
+fn transpose_bars(
+    notes: Vec<NoteData>,
+    scale_notes: &Vec<Note>,
+    rate: f64,
+    bar_time: DeltaTime,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
+    let bars = notes
+        .into_iter()
+        .group_by(|note| note.start() / bar_time)
+        .into_iter()
+        .map(|(_, bar)| bar.collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    bars.into_iter()
+        .flat_map(|bar| match rng.gen_bool(rate) {
+            true => {
+                let direction = rng.gen::<u32>() % 2;
+                randomize_lead(bar, scale_notes, direction, rng)
+            }
+
+            false => bar,
+        })
+        .collect()
+}
+
+/// Time range the note can occupy: from the end of the previous note
+/// to the start of the next one, limited by the bar of the note
+
+#[inline]
+fn free_space(notes: &Vec<NoteData>, i: usize, bar_time: DeltaTime) -> (DeltaTime, DeltaTime) {
+    let bar_start = notes[i].start() / bar_time * bar_time;
+
+    let prev_end = i.checked_sub(1).map(|prev| end(&notes[prev])).unwrap_or(0);
+
+    let next_start = notes
+        .get(i + 1)
+        .map(|next| next.start())
+        .unwrap_or(DeltaTime::MAX);
+
+    (
+        prev_end.max(bar_start),
+        next_start.min(bar_start + bar_time),
+    )
+}
+
+/// Time when the note ends
+
+#[inline]
+fn end(note: &NoteData) -> DeltaTime {
+    note.start() + note.length()
+}
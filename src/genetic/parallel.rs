@@ -0,0 +1,54 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+
+/// Constructs the pool with the given number of threads
+/// (all available cores if it is not specified).
+/// Pool is created once per run and shared by all generations
+
+#[inline]
+pub fn thread_pool(threads: Option<usize>) -> Result<ThreadPool, ThreadPoolBuildError> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads.unwrap_or(0))
+        .build()
+}
+
+/// Maps items on the threads of the given pool.
+/// Results are in the same order as items
+
+#[inline]
+pub fn par_map<T: Sync, R: Send>(
+    pool: &ThreadPool,
+    items: &[T],
+    f: impl Fn(&T) -> R + Sync + Send,
+) -> Vec<R> {
+    pool.install(|| items.par_iter().map(f).collect())
+}
+
+/// Produces the given number of results on the threads of the given pool.
+/// Every result has its own RNG, seeded from the given one,
+/// so results are the same for the same seed regardless of the number of threads
+///
+/// # Example
+/// ```
+/// use music_generator::genetic::parallel::{par_generate, thread_pool};
+/// use rand::{rngs::StdRng, Rng, SeedableRng};
+///
+/// let generate = |threads| {
+///     let pool = thread_pool(Some(threads)).unwrap();
+///     let mut rng = StdRng::seed_from_u64(42);
+///     par_generate(&pool, 100, &mut rng, |rng| rng.gen::<u32>())
+/// };
+///
+/// assert_eq!(generate(1), generate(8))
+/// ```
+
+#[inline]
+pub fn par_generate<R: Send>(
+    pool: &ThreadPool,
+    count: usize,
+    rng: &mut impl Rng,
+    f: impl Fn(&mut StdRng) -> R + Sync + Send,
+) -> Vec<R> {
+    let seeds = (0..count).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+    par_map(pool, &seeds, |&seed| f(&mut StdRng::seed_from_u64(seed)))
+}
@@ -1,41 +1,244 @@
+use itertools::Itertools;
 use rand::Rng;
 
-/// Performs the selection of leads with the roulette algorithm
-/// and the desired fitness to generate the next population.
+/// Selection operator of the genetic algorithm:
+/// chooses leads (possibly repeatedly) that become
+/// parents of the next population by their fitness values
+
+pub trait Selection {
+    /// Chooses `count` indexes of the population
+    /// with the given fitness values
+
+    fn select_indexes(
+        &self,
+        fitness_values: &Vec<f32>,
+        count: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<usize>;
+
+    /// Selects as many leads as there are in the population
+
+    #[inline]
+    fn select<T: Clone>(
+        &self,
+        population: &Vec<T>,
+        fitness_values: &Vec<f32>,
+        rng: &mut impl Rng,
+    ) -> Vec<T> {
+        self.select_indexes(fitness_values, population.len(), rng)
+            .into_iter()
+            .map(|i| population[i].clone())
+            .collect()
+    }
+}
+
+/// Fitness proportionate selection: every lead is chosen
+/// with the probability equal to its share of the total fitness.
+/// If all leads have zero fitness, they are selected uniformly
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Roulette;
+
+/// Every lead is the fittest one among `size`
+/// randomly chosen leads of the population
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Tournament {
+    pub size: usize,
+}
+
+/// Roulette selection by the rank of the lead instead of its fitness:
+/// the least fit lead has weight 1, the fittest one has weight N.
+/// Leads with zero fitness can still be selected
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Rank;
+
+/// Roulette with the equally spaced pointers that are spun once.
+/// Number of copies of every lead is close to its expected one.
+/// If all leads have zero fitness, they are selected uniformly
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct StochasticUniversalSampling;
+
+/// Selection operators that can be chosen in the [super::config::GeneticConfig]
+///
+/// # Example
+/// ```
+/// use music_generator::genetic::selection::{Selection, SelectionMethod, Tournament};
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let mut rng = StdRng::seed_from_u64(0);
+/// let population = vec!['a', 'b', 'c', 'd'];
+/// let fitness_values = vec![0.0, 0.0, 0.0, 1.0];
+///
+/// let method = SelectionMethod::Tournament(Tournament { size: 4 });
+/// let selected = method.select(&population, &fitness_values, &mut rng);
+/// assert_eq!(selected.len(), 4);
+///
+/// // All leads may have zero fitness
+/// let selected = SelectionMethod::Roulette.select(&population, &vec![0.0; 4], &mut rng);
+/// assert_eq!(selected.len(), 4)
+/// ```
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SelectionMethod {
+    #[default]
+    Roulette,
+    Tournament(Tournament),
+    Rank,
+    StochasticUniversalSampling,
+}
+
+impl Selection for Roulette {
+    #[inline]
+    fn select_indexes(
+        &self,
+        fitness_values: &Vec<f32>,
+        count: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<usize> {
+        spin_roulette(fitness_values, count, rng)
+    }
+}
+
+impl Selection for Tournament {
+    #[inline]
+    fn select_indexes(
+        &self,
+        fitness_values: &Vec<f32>,
+        count: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<usize> {
+        if fitness_values.is_empty() {
+            return Vec::new();
+        }
+
+        (0..count)
+            .map(|_| {
+                (0..self.size.max(1))
+                    .map(|_| rng.gen_range(0..fitness_values.len()))
+                    .max_by(|&x, &y| fitness_values[x].total_cmp(&fitness_values[y]))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+impl Selection for Rank {
+    #[inline]
+    fn select_indexes(
+        &self,
+        fitness_values: &Vec<f32>,
+        count: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<usize> {
+        let ranked = (0..fitness_values.len())
+            .sorted_by(|&x, &y| fitness_values[x].total_cmp(&fitness_values[y]))
+            .collect::<Vec<_>>();
+
+        let ranks = (1..=ranked.len()).map(|rank| rank as f32).collect();
+
+        spin_roulette(&ranks, count, rng)
+            .into_iter()
+            .map(|i| ranked[i])
+            .collect()
+    }
+}
+
+impl Selection for StochasticUniversalSampling {
+    #[inline]
+    fn select_indexes(
+        &self,
+        fitness_values: &Vec<f32>,
+        count: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<usize> {
+        let fitness_sums = fitness_sums(fitness_values);
+
+        let total_fitness = match fitness_sums.last() {
+            Some(&total_fitness) if total_fitness > 0.0 && count > 0 => total_fitness,
+            _ => return select_uniformly(fitness_values.len(), count, rng),
+        };
+
+        let step = total_fitness / count as f32;
+        let start = rng.gen_range(0.0..step);
+
+        (0..count)
+            .map(|i| find_in_sums(&fitness_sums, start + i as f32 * step))
+            .collect()
+    }
+}
+
+impl Selection for SelectionMethod {
+    #[inline]
+    fn select_indexes(
+        &self,
+        fitness_values: &Vec<f32>,
+        count: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<usize> {
+        match self {
+            SelectionMethod::Roulette => Roulette.select_indexes(fitness_values, count, rng),
+            SelectionMethod::Tournament(tournament) => {
+                tournament.select_indexes(fitness_values, count, rng)
+            }
+            SelectionMethod::Rank => Rank.select_indexes(fitness_values, count, rng),
+            SelectionMethod::StochasticUniversalSampling => {
+                StochasticUniversalSampling.select_indexes(fitness_values, count, rng)
+            }
+        }
+    }
+}
+
+/// Chooses `count` indexes with the probability
+/// proportional to the given weights.
+/// If total weight is zero, indexes are chosen uniformly
 
 #[inline]
-pub fn select_from_population_with_roulette<T: Clone>(
-    population: &Vec<T>,
-    fitness_values: Vec<f32>,
-) -> Vec<T> {
-    let mut rng = rand::thread_rng();
-
-    let fitness_sums = fitness_sums(fitness_values);
-    let total_fitness = fitness_sums.last().unwrap().1;
-
-    (0..population.len())
-        .map(|_| {
-            let roulette_value = rng.gen_range(0.0..total_fitness);
-
-            fitness_sums
-                .iter()
-                .find(|(_, fitness)| *fitness > roulette_value)
-                .map(|(i, _)| population[*i].clone())
-        })
-        .flatten()
+fn spin_roulette(weights: &Vec<f32>, count: usize, rng: &mut impl Rng) -> Vec<usize> {
+    let fitness_sums = fitness_sums(weights);
+
+    let total_fitness = match fitness_sums.last() {
+        Some(&total_fitness) if total_fitness > 0.0 => total_fitness,
+        _ => return select_uniformly(weights.len(), count, rng),
+    };
+
+    (0..count)
+        .map(|_| find_in_sums(&fitness_sums, rng.gen_range(0.0..total_fitness)))
         .collect()
 }
 
-/// Generates prefix sums vector from the given fitness values
+/// Chooses `count` random indexes in range `0..len`
+
+#[inline]
+fn select_uniformly(len: usize, count: usize, rng: &mut impl Rng) -> Vec<usize> {
+    match len {
+        0 => Vec::new(),
+        _ => (0..count).map(|_| rng.gen_range(0..len)).collect(),
+    }
+}
+
+/// Index of the first prefix sum that is greater than the value
+
+#[inline]
+fn find_in_sums(fitness_sums: &Vec<f32>, value: f32) -> usize {
+    fitness_sums
+        .iter()
+        .position(|&sum| sum > value)
+        .unwrap_or(fitness_sums.len() - 1)
+}
+
+/// Generates prefix sums vector from the given fitness values.
+/// Negative fitness values are treated as zero ones
 
 #[inline]
-fn fitness_sums(fitness_values: Vec<f32>) -> Vec<(usize, f32)> {
+fn fitness_sums(fitness_values: &Vec<f32>) -> Vec<f32> {
     fitness_values
-        .into_iter()
-        .scan(0.0, |acc, x| {
-            *acc = *acc + x;
+        .iter()
+        .scan(0.0, |acc, &x| {
+            *acc += x.max(0.0);
             Some(*acc)
         })
-        .enumerate()
         .collect::<Vec<_>>()
 }
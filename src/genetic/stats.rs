@@ -0,0 +1,100 @@
+use crate::{error::Error, genetic::fitness::FitnessComponents, notes::note_data::NoteData};
+use std::{path::PathBuf, time::Duration};
+
+/// Progress of the genetic algorithm
+/// after the population of the generation was evaluated
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GenerationStats {
+    /// Number of the generation, initial population is the 0-th one
+    pub generation: usize,
+
+    /// Maximum fitness of the population
+    pub best_fitness: f32,
+
+    /// Mean fitness of the population
+    pub mean_fitness: f32,
+}
+
+impl GenerationStats {
+    /// Collects statistics of the evaluated generation
+
+    #[inline]
+    pub fn new(generation: usize, fitness_values: &Vec<f32>) -> Self {
+        let best_fitness = fitness_values
+            .iter()
+            .copied()
+            .max_by(|x, y| x.total_cmp(y))
+            .unwrap_or(0.0);
+
+        let mean_fitness = match fitness_values.is_empty() {
+            true => 0.0,
+            false => fitness_values.iter().sum::<f32>() / fitness_values.len() as f32,
+        };
+
+        Self {
+            generation,
+            best_fitness,
+            mean_fitness,
+        }
+    }
+}
+
+/// Reason why the genetic algorithm has stopped
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Termination {
+    /// Desired fitness was reached
+    DesiredFitness,
+
+    /// Maximum number of generations was evaluated
+    MaxGenerations,
+
+    /// Time budget was spent
+    TimeBudget,
+
+    /// Best fitness was not improved for the stagnation limit of generations
+    Stagnation,
+}
+
+/// Best lead found by the genetic algorithm
+/// with the statistics of the run
+
+#[derive(Debug)]
+pub struct GeneticResult {
+    /// Fittest lead among all generations
+    pub lead: Vec<NoteData>,
+
+    /// Fitness of the lead
+    pub fitness: f32,
+
+    /// Separate components of the lead's fitness
+    pub components: FitnessComponents,
+
+    /// Sample file the lead is the most similar to
+    pub sample: PathBuf,
+
+    /// All sample files the lead was learned from
+    pub samples: Vec<PathBuf>,
+
+    /// Errors of the sample files that were skipped
+    pub skipped_samples: Vec<Error>,
+
+    /// Statistics of all evaluated generations, starting from the initial one
+    pub history: Vec<GenerationStats>,
+
+    /// Reason why the algorithm has stopped
+    pub termination: Termination,
+
+    /// Time of the algorithm's work
+    pub elapsed: Duration,
+}
+
+impl GeneticResult {
+    /// Number of generations after the initial one
+
+    #[inline]
+    pub fn generations(&self) -> usize {
+        self.history.len().saturating_sub(1)
+    }
+}
@@ -3,10 +3,13 @@ use std::{
     slice::Iter,
 };
 
+pub mod error;
 pub mod genetic;
 pub mod melody_type;
 pub mod midi;
 pub mod notes;
+pub mod rules;
+pub mod song;
 
 trait WithNextIterable<'a, T, I: Iterator>: IntoIterator {
     fn to_iter(&'a self) -> I;
@@ -1,29 +1,29 @@
 use chrono::Local;
-
-use ghakuf::{
-    messages::{Message, MetaEvent, MidiEvent},
-    writer::Writer,
-};
+use cli::{parse_genetic_rate, CliArgs, CliError, USAGE};
 
 use music_generator::{
+    genetic::{
+        config::GeneticConfig, generate_lead_with_genetic_algorithm, stats::GenerationStats,
+    },
     midi::{
-        bpm::BPM,
-        generator::{composer::*, generator::generate_bpm},
-        key_list, melody_types, mode_list, scale_list,
+        bpm::BPM, generator::generator::generate_bpm, key_list, melody_types, mode_list,
+        parser::midi_file_manager::DEFAULT_SAMPLES_PATH, scale_list, scale_notes,
     },
-    notes::note::Note,
+    song::builder::SongBuilder,
 };
 
-use rust_music_theory::{note::Notes, scale::*};
-use std::{fmt::Debug, fs::File, io::Write, path::Path};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{fs::File, io::Write, path::PathBuf};
+
+mod cli;
 
 #[inline]
-fn select_from_list<T: Clone + Debug>(inp_msg: &str, list: Vec<T>) -> T {
+fn select_from_list<T: Clone>(inp_msg: &str, list: Vec<T>, name: impl Fn(&T) -> String) -> T {
     println!("{inp_msg}");
 
     list.iter()
         .enumerate()
-        .for_each(|(ind, v)| println!("{}. {:?}", ind + 1, v.clone()));
+        .for_each(|(ind, v)| println!("{}. {}", ind + 1, name(v)));
 
     let mut input = String::new();
 
@@ -35,109 +35,217 @@ fn select_from_list<T: Clone + Debug>(inp_msg: &str, list: Vec<T>) -> T {
     list.get(index - 1).expect("Wrong index").clone()
 }
 
-#[monoio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut midi_writer = Writer::new();
-    midi_writer.running_status(true);
-
-    let key = select_from_list("Select key's number:", key_list());
-    let scale = select_from_list("Select scale's number:", scale_list());
-    let mode = select_from_list("Select mode's number:", mode_list());
-    let melody_type = select_from_list("Select melody type's number:", melody_types());
-
-    // Picking all notes in 5 octave.
-    // This notes will help to construct
-    // both lead melody and chords in harmony
-
-    let scale_notes = (4..=5)
-        .map(|octave| {
-            Scale::new(scale, key, octave, Some(mode), Direction::Ascending)
-                .unwrap()
-                .notes()
-                .into_iter()
-                .map(Note::from)
-        })
-        .flatten()
-        .collect::<Vec<_>>();
-
-    println!("SCALE NOTES: {:?}\n", scale_notes);
-
-    let bpm = generate_bpm();
-    let generated_lead = melody_type.generate_synthwave_melody(key, &scale_notes);
-
-    println!("BPM: {}", bpm);
-    println!("LEAD: {:?}", generated_lead);
-
-    let lead_midi_messages = compose_lead_from_generated(generated_lead, compose_note);
-
-    let tempo = bpm.tempo();
-
-    let tempo_msg = Message::MetaEvent {
-        delta_time: 0,
-        event: MetaEvent::SetTempo,
-        data: vec![(tempo >> 16) as u8, (tempo >> 8) as u8, tempo as u8],
-    };
+#[inline]
+fn read_genetic_rate<E>(inp_msg: &str, error: E) -> f32
+where
+    E: FnOnce(String) -> CliError,
+{
+    println!("{inp_msg}");
 
-    let end_of_track_msg = Message::MetaEvent {
-        delta_time: 0,
-        event: MetaEvent::EndOfTrack,
-        data: Vec::new(),
-    };
+    let mut input = String::new();
 
-    let track_change_msg = Message::TrackChange;
+    std::io::stdin()
+        .read_line(&mut input)
+        .expect("Unable to read string");
 
-    // 1, 53, 29, 120, 48, 117, 109,
-    // 67, 88, 58, 60, 52, 79, 61, 48, 5, 87, 27, 33
-    // 5, 32, 48, 63, 85
+    parse_genetic_rate(&input, error).expect("Wrong number input")
+}
 
-    let lead_instrument_msg = Message::MidiEvent {
-        delta_time: 0,
-        event: MidiEvent::ProgramChange { ch: 0, program: 80 },
-    };
+#[inline]
+fn print_generation_stats(stats: GenerationStats) {
+    println!(
+        "GENERATION {}: best fitness {:.4}, mean fitness {:.4}",
+        stats.generation, stats.best_fitness, stats.mean_fitness
+    );
+}
 
-    let reverb_effect_msg = Message::MidiEvent {
-        delta_time: 0,
-        event: MidiEvent::ControlChange {
-            ch: 0,
-            control: 91,
-            data: 120,
-        },
+#[monoio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = match CliArgs::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+
+        Err(CliError::Help) => {
+            println!("{USAGE}");
+            return Ok(());
+        }
+
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            std::process::exit(2);
+        }
     };
 
-    let chorus_effect_msg = Message::MidiEvent {
-        delta_time: 0,
-        event: MidiEvent::ControlChange {
-            ch: 0,
-            control: 93,
-            data: 90,
-        },
+    // Values that were not passed as flags are requested interactively
+
+    let key = args.key.unwrap_or_else(|| {
+        select_from_list("Select key's number:", key_list(), |v| format!("{v:?}"))
+    });
+
+    let scale = args.scale.unwrap_or_else(|| {
+        select_from_list("Select scale's number:", scale_list(), |v| format!("{v:?}"))
+    });
+
+    let mode = args.mode.unwrap_or_else(|| {
+        select_from_list("Select mode's number:", mode_list(), |v| format!("{v:?}"))
+    });
+
+    let melody_type = args.melody_type.unwrap_or_else(|| {
+        select_from_list(
+            "Select melody type's number:",
+            melody_types(),
+            ToString::to_string,
+        )
+    });
+
+    // Every random decision is taken with the seeded RNG,
+    // so the same seed and settings produce the same file
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+
+    let mut builder = SongBuilder::new()
+        .key(key)
+        .scale(scale)
+        .mode(mode)
+        .melody_type(melody_type)
+        .seed(seed);
+
+    if let Some(bpm) = args.bpm {
+        builder = builder.bpm(bpm);
+    }
+
+    // Lead is either generated with the rules of the melody type
+    // or learned from the samples with the genetic algorithm
+
+    let generator = match args.genetic {
+        false => String::from("rules"),
+
+        true => {
+            let desired_fitness = args.desired_fitness.unwrap_or_else(|| {
+                read_genetic_rate("Enter desired fitness (0..=1):", CliError::InvalidFitness)
+            });
+
+            let mutation_rate = args.mutation_rate.unwrap_or_else(|| {
+                read_genetic_rate(
+                    "Enter mutation rate (0..=1):",
+                    CliError::InvalidMutationRate,
+                )
+            });
+
+            let config = GeneticConfig {
+                samples_path: args
+                    .samples
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(DEFAULT_SAMPLES_PATH)),
+                ..GeneticConfig::new(desired_fitness, mutation_rate)
+            };
+
+            let mut rng = StdRng::seed_from_u64(seed);
+            let bpm = args.bpm.unwrap_or_else(|| generate_bpm(&mut rng).as_u64());
+
+            let result = generate_lead_with_genetic_algorithm(
+                key,
+                &scale_notes(key, scale, mode, 4..=5)?,
+                melody_type,
+                &config,
+                &mut rng,
+                print_generation_stats,
+            )
+            .await?;
+
+            result
+                .skipped_samples
+                .iter()
+                .for_each(|err| eprintln!("Skipping sample: {err}"));
+
+            println!("SAMPLES: {:?}", result.samples);
+
+            println!(
+                "GENETIC: {:?} after {} generations, fitness {:.4}",
+                result.termination,
+                result.generations(),
+                result.fitness
+            );
+
+            println!("FITNESS COMPONENTS: {:?}", result.components);
+            println!("NEAREST SAMPLE: {:?}", result.sample);
+
+            builder = builder.bpm(bpm).lead(result.lead);
+
+            format!(
+                "genetic {desired_fitness} {mutation_rate} {:?}",
+                config.samples_path
+            )
+        }
     };
 
-    // Initialise MIDI file with tempo and instrument
-
-    midi_writer.format(1);
-    midi_writer.time_base(128);
-    midi_writer.push(&tempo_msg);
-    midi_writer.push(&end_of_track_msg);
-
-    // Pushes lead messages to the event holder
-    midi_writer.push(&track_change_msg);
-    midi_writer.push(&lead_instrument_msg);
-    midi_writer.push(&reverb_effect_msg);
-    midi_writer.push(&chorus_effect_msg);
-    lead_midi_messages.iter().for_each(|m| midi_writer.push(m));
-    midi_writer.push(&end_of_track_msg);
-
-    std::fs::create_dir("./generated").unwrap_or_default();
+    if let Some(time_signature) = args.time_signature {
+        builder = builder.time_signature(time_signature);
+    }
+
+    if let Some(resolution) = args.resolution {
+        builder = builder.resolution(resolution);
+    }
+
+    if let Some(arrangement) = args.arrangement {
+        builder = builder.arrangement(arrangement);
+    }
+
+    let song = builder.build()?;
+    let accompaniment = song.accompaniment;
+
+    if let Some(warning) = song.generation.as_ref().and_then(|gen| gen.warning()) {
+        eprintln!("Warning: {warning}");
+    }
+
+    println!("SCALE NOTES: {:?}\n", song.scale_notes);
+    println!("SEED: {}", song.seed);
+    println!("BPM: {}", song.bpm);
+    println!("TIME SIGNATURE: {}", song.time_signature);
+    println!("LEAD: {:?}", song.lead);
+    println!("PROGRESSION: {:?}", accompaniment.progression);
+    println!("HARMONY: {:?}", song.harmony);
+    println!(
+        "ARPEGGIO: {:?} {:?} ({} octaves)",
+        accompaniment.arpeggio_rate, accompaniment.arpeggio_pattern, accompaniment.arpeggio_octaves
+    );
+    println!("BASS: {:?}", accompaniment.bass_pattern);
+    println!("DRUMS: {:?}", accompaniment.drum_pattern);
+
+    if let Some(arrangement) = &song.arrangement {
+        println!("ARRANGEMENT: {}", arrangement);
+    }
+
+    let out_dir = args.out.unwrap_or_else(|| PathBuf::from("./generated"));
+    std::fs::create_dir_all(&out_dir)?;
 
     let mut file = File::create("track_settings.txt")?;
-    let track_settings = format!("{key}\n{scale}\n{mode}\n{:?}\n{bpm}", melody_type);
+    let track_settings = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{:?}\n{:?} {:?} {}\n{:?}\n{:?}\n{}\n{}",
+        song.key,
+        song.scale,
+        song.mode,
+        song.melody_type,
+        song.bpm,
+        song.time_signature,
+        song.resolution,
+        song.seed,
+        accompaniment.progression,
+        accompaniment.arpeggio_rate,
+        accompaniment.arpeggio_pattern,
+        accompaniment.arpeggio_octaves,
+        accompaniment.bass_pattern,
+        accompaniment.drum_pattern,
+        song.arrangement
+            .as_ref()
+            .map(|arrangement| arrangement.to_string())
+            .unwrap_or_else(|| String::from("loop")),
+        generator
+    );
     file.write_all(track_settings.as_bytes())?;
 
-    let path = format!("./generated/{}-{}BPM-{}.mid", key, bpm, Local::now());
-    let path = Path::new(path.as_str());
-    println!("PATH: {:?}", path.to_path_buf());
+    let path = out_dir.join(format!("{}-{}BPM-{}.mid", song.key, song.bpm, Local::now()));
+    println!("PATH: {:?}", path);
 
-    midi_writer.write(&path)?;
+    song.write(&path)?;
     Ok(())
 }
@@ -1,34 +1,294 @@
 use crate::{
-    genetic::{fitness::*, mutation::mutate},
-    midi::generator::generator::{
-        generate_lead_melody_with_bpm_and_len, generate_synthwave_melody_length,
-    },
-    notes::{
-        note::Note,
-        note_data::{DeltaTime, NoteData},
+    error::{Error, Result},
+    genetic::mutation::mutate,
+    midi::{
+        generator::{
+            cadence::{resolve_to_pitch_class, with_cadences, CadencePlan},
+            generator::{generate_lead_melody_with_bpm_and_len, generate_synthwave_melody_length},
+            join_bars,
+            phrase::PhraseTransform,
+            time_before_bar_end,
+        },
+        melody_types,
+        time_signature::TimeSignature,
     },
+    notes::{note::Note, note_data::NoteData},
+    rules::{RuleReport, RuleSet},
 };
 
+use itertools::Itertools;
+use rand::Rng;
 use rust_music_theory::note::PitchClass;
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// Probability of every note's pitch to be changed in the mutated phrases
+const PHRASE_MUTATION_RATE: f32 = 0.75;
+
+/// Maximum number of attempts to generate the phrase
+/// that satisfies the local rules
+const PHRASE_ATTEMPTS: usize = 10;
+
+/// Marks of the phrase transforms in the form's notation.
+/// Sequence by a step down is written as `-`
+const TRANSFORM_MARKS: [(char, PhraseTransform); 8] = [
+    ('+', PhraseTransform::Sequence(1)),
+    ('-', PhraseTransform::Sequence(-1)),
+    ('~', PhraseTransform::Inversion),
+    ('<', PhraseTransform::Retrograde),
+    ('*', PhraseTransform::Augmentation),
+    ('/', PhraseTransform::Diminution),
+    ('^', PhraseTransform::Ornamentation),
+    ('.', PhraseTransform::Truncation),
+];
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum SynthwaveMelodyType {
     ABAB,
     AAAB,
     ABAC,
+    AABA,
+    ABCD,
+    AABB,
+
+    /// Call (A), response (B), the call moved a step down
+    /// and the response that resolves to the tonic: `ABA-B'`
+    CallAndResponse,
+
+    /// ABAB where the last B resolves to the tonic: `ABAB'`
+    ABABCadence,
+
+    /// User-defined form (e.g. `AAB'A`), see [MelodyForm]
+    Custom(MelodyForm),
+
+    /// ABAB, AAAB or ABAC with the cadence plan
+    /// that differs from its own (e.g. `ABAB|_,1`)
+    Mutated(MutatedForm, CadencePlan),
 }
 
 impl SynthwaveMelodyType {
+    /// Default number of candidates generated before
+    /// the closest to the rules one is accepted
+    pub const MAX_ATTEMPTS: usize = 1000;
+
+    /// Parts of the 4 bar phrase, one letter per bar.
+    /// Same letters mean the same (or mutated) material
+    ///
+    /// # Example
+    /// ```
+    /// use music_generator::melody_type::SynthwaveMelodyType;
+    /// assert_eq!(SynthwaveMelodyType::ABAC.form(), ['A', 'B', 'A', 'C'])
+    /// ```
+
+    #[inline]
+    pub fn form(&self) -> [char; 4] {
+        match self {
+            SynthwaveMelodyType::ABAB => ['A', 'B', 'A', 'B'],
+            SynthwaveMelodyType::AAAB => ['A', 'A', 'A', 'B'],
+            SynthwaveMelodyType::ABAC => ['A', 'B', 'A', 'C'],
+            SynthwaveMelodyType::AABA => ['A', 'A', 'B', 'A'],
+            SynthwaveMelodyType::ABCD => ['A', 'B', 'C', 'D'],
+            SynthwaveMelodyType::AABB => ['A', 'A', 'B', 'B'],
+            SynthwaveMelodyType::Mutated(form, _) => SynthwaveMelodyType::from(*form).form(),
+            _ => self.phrases().letters(),
+        }
+    }
+
+    /// Phrases of the forms that are built from the letters.
+    /// ABAB, AAAB and ABAC have their own generation
+    /// (B and C are mutations of A), so their phrases are plain letters
+
+    #[inline]
+    pub fn phrases(&self) -> MelodyForm {
+        use PhraseVariation::*;
+
+        let phrases = |phrases: [(char, PhraseVariation); 4]| {
+            MelodyForm::new(phrases.map(|(letter, variation)| FormPhrase::new(letter, variation)))
+                .with_cadences(self.cadences())
+        };
+
+        match self {
+            SynthwaveMelodyType::Custom(form) => *form,
+
+            SynthwaveMelodyType::CallAndResponse => phrases([
+                ('A', Original),
+                ('B', Original),
+                ('A', Transformed(PhraseTransform::Sequence(-1))),
+                ('B', Cadence),
+            ]),
+
+            SynthwaveMelodyType::ABABCadence => phrases([
+                ('A', Original),
+                ('B', Original),
+                ('A', Original),
+                ('B', Cadence),
+            ]),
+
+            _ => phrases(self.form().map(|letter| (letter, Original))),
+        }
+    }
+
+    /// Degrees that the phrases of the form end on.
+    /// Forms that resolve to the tonic in the 4-th bar
+    /// (ABAB' and call and response) have no half cadence
+
+    #[inline]
+    pub fn cadences(&self) -> CadencePlan {
+        match self {
+            SynthwaveMelodyType::Custom(form) => form.cadences,
+            SynthwaveMelodyType::Mutated(_, cadences) => *cadences,
+            SynthwaveMelodyType::CallAndResponse | SynthwaveMelodyType::ABABCadence => {
+                CadencePlan::AUTHENTIC
+            }
+            _ => CadencePlan::HALF_AND_AUTHENTIC,
+        }
+    }
+
+    /// Generates 8 bar lead melody of the given form
+    /// that satisfies the rules of the melody generation ([RuleSet::generation]).
+    /// If no candidate satisfies them in [Self::MAX_ATTEMPTS] attempts,
+    /// the candidate with the least violations is used.
+    /// Returns [Error::EmptyScale] if there are no notes to pick from
+
     #[inline]
     pub fn generate_synthwave_melody(
         &self,
         key: PitchClass,
         scale_notes: &Vec<Note>,
-    ) -> Vec<NoteData> {
-        match self.try_generate_synthwave_melody(key, scale_notes) {
-            None => self.generate_synthwave_melody(key, scale_notes),
-            Some(lead) => lead,
+        time_signature: TimeSignature,
+        rng: &mut impl Rng,
+    ) -> Result<Vec<NoteData>> {
+        self.generate_synthwave_melody_with_rules(
+            key,
+            scale_notes,
+            time_signature,
+            &RuleSet::generation(),
+            rng,
+        )
+        .map(|generation| generation.lead)
+    }
+
+    /// Generates 8 bar lead melody of the given form
+    /// that satisfies all given rules.
+    /// If no candidate satisfies them in [Self::MAX_ATTEMPTS] attempts,
+    /// the candidate with the least violations is used
+    /// and the violated rules are reported in the result
+    /// (see [MelodyGeneration::warning]).
+    /// Returns [Error::EmptyScale] if there are no notes to pick from
+
+    #[inline]
+    pub fn generate_synthwave_melody_with_rules(
+        &self,
+        key: PitchClass,
+        scale_notes: &Vec<Note>,
+        time_signature: TimeSignature,
+        rules: &RuleSet,
+        rng: &mut impl Rng,
+    ) -> Result<MelodyGeneration> {
+        self.generate_synthwave_melody_with_diagnostics(
+            key,
+            scale_notes,
+            time_signature,
+            rules,
+            Self::MAX_ATTEMPTS,
+            rng,
+        )
+    }
+
+    /// Generates candidates of 8 bar lead melody until one satisfies all rules
+    /// or the given number of attempts (at least one) is spent.
+    /// Collects how many candidates every rule has rejected.
+    /// If the budget is spent, returns the candidate with the least violations
+    /// together with its report.
+    /// Returns [Error::EmptyScale] if there are no notes to pick from
+    ///
+    /// # Example
+    /// ```
+    /// use music_generator::{
+    ///     melody_type::SynthwaveMelodyType,
+    ///     midi::{scale_notes, time_signature::TimeSignature},
+    ///     rules::{builtin::LeapLimit, RuleSet},
+    /// };
+    /// use rust_music_theory::{note::PitchClass, scale::{Mode, ScaleType}};
+    ///
+    /// let notes = scale_notes(PitchClass::C, ScaleType::Diatonic, Mode::Aeolian, 4..=5).unwrap();
+    /// let impossible = RuleSet::new().with_rule(LeapLimit { limit: 0 });
+    ///
+    /// let generation = SynthwaveMelodyType::ABAC
+    ///     .generate_synthwave_melody_with_diagnostics(
+    ///         PitchClass::C,
+    ///         &notes,
+    ///         TimeSignature::COMMON,
+    ///         &impossible,
+    ///         10,
+    ///         &mut rand::thread_rng(),
+    ///     )
+    ///     .unwrap();
+    ///
+    /// assert_eq!(generation.attempts, 10);
+    /// assert_eq!(generation.rejections["leap"], 10);
+    /// assert!(!generation.report.is_satisfied());
+    /// assert!(generation.warning().is_some());
+    /// assert!(!generation.lead.is_empty())
+    /// ```
+
+    #[inline]
+    pub fn generate_synthwave_melody_with_diagnostics(
+        &self,
+        key: PitchClass,
+        scale_notes: &Vec<Note>,
+        time_signature: TimeSignature,
+        rules: &RuleSet,
+        max_attempts: usize,
+        rng: &mut impl Rng,
+    ) -> Result<MelodyGeneration> {
+        if scale_notes.is_empty() {
+            return Err(Error::EmptyScale);
+        }
+
+        let mut candidate = || {
+            self.try_generate_synthwave_melody(key, scale_notes, time_signature, rules, rng)
+                .map(|lead| {
+                    let report = rules.check(&lead);
+                    (lead, report)
+                })
+        };
+
+        let mut rejections = BTreeMap::new();
+        let mut count_rejections = |report: &RuleReport| {
+            report
+                .violations
+                .iter()
+                .for_each(|violation| *rejections.entry(violation.rule.clone()).or_insert(0) += 1)
+        };
+
+        // The first candidate is the best one so far,
+        // next ones replace it if they have less violations.
+        // Satisfying candidate has no violations and stops the search
+
+        let (mut lead, mut report) = candidate()?;
+        let mut attempts = 1;
+        count_rejections(&report);
+
+        while !report.is_satisfied() && attempts < max_attempts {
+            let (next_lead, next_report) = candidate()?;
+            attempts += 1;
+            count_rejections(&next_report);
+
+            if next_report.violations.len() < report.violations.len() {
+                (lead, report) = (next_lead, next_report);
+            }
         }
+
+        Ok(MelodyGeneration {
+            lead,
+            attempts,
+            rejections,
+            report,
+        })
     }
 
     #[inline]
@@ -36,24 +296,515 @@ impl SynthwaveMelodyType {
         &self,
         key: PitchClass,
         scale_notes: &Vec<Note>,
-    ) -> Option<Vec<NoteData>> {
-        let melody_length = generate_synthwave_melody_length();
+        time_signature: TimeSignature,
+        rules: &RuleSet,
+        rng: &mut impl Rng,
+    ) -> Result<Vec<NoteData>> {
+        let melody_length = generate_synthwave_melody_length(rng);
 
         let lead = match self {
-            SynthwaveMelodyType::ABAB => generate_abab_melody(key, scale_notes, melody_length),
-            SynthwaveMelodyType::AAAB => generate_aaab_melody(key, scale_notes, melody_length),
-            SynthwaveMelodyType::ABAC => generate_abac_melody(key, scale_notes, melody_length),
+            SynthwaveMelodyType::ABAB | SynthwaveMelodyType::Mutated(MutatedForm::ABAB, _) => {
+                generate_abab_melody(key, scale_notes, melody_length, time_signature, rng)
+            }
+            SynthwaveMelodyType::AAAB | SynthwaveMelodyType::Mutated(MutatedForm::AAAB, _) => {
+                generate_aaab_melody(key, scale_notes, melody_length, time_signature, rng)
+            }
+            SynthwaveMelodyType::ABAC | SynthwaveMelodyType::Mutated(MutatedForm::ABAC, _) => {
+                generate_abac_melody(key, scale_notes, melody_length, time_signature, rng)
+            }
+            _ => generate_form_melody(
+                key,
+                scale_notes,
+                melody_length,
+                time_signature,
+                &self.phrases(),
+                rules,
+                rng,
+            ),
+        }?;
+
+        Ok(with_cadences(
+            lead,
+            self.cadences(),
+            key,
+            scale_notes,
+            time_signature,
+        ))
+    }
+}
+
+impl Display for SynthwaveMelodyType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SynthwaveMelodyType::ABABCadence => write!(f, "ABAB'"),
+            SynthwaveMelodyType::Custom(form) => write!(f, "{form}"),
+            SynthwaveMelodyType::Mutated(form, cadences) => write!(f, "{form:?}|{cadences}"),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl FromStr for SynthwaveMelodyType {
+    type Err = MelodyFormParseError;
+
+    /// Parses either the name of the form (e.g. `AABA`, `ABAB'`, `CallAndResponse`)
+    /// or the user-defined form (e.g. `AAB'A`), optionally followed by
+    /// the cadence plan after `|` (see [MelodyForm]). Named form with
+    /// a plan that differs from its own is built from its letters as the custom one,
+    /// except ABAB, AAAB and ABAC that keep their own generation ([Self::Mutated])
+    ///
+    /// # Example
+    /// ```
+    /// use music_generator::{
+    ///     melody_type::{MutatedForm, SynthwaveMelodyType},
+    ///     midi::generator::cadence::CadencePlan,
+    /// };
+    ///
+    /// assert_eq!("aaba".parse(), Ok(SynthwaveMelodyType::AABA));
+    /// assert_eq!("ABAB'".parse(), Ok(SynthwaveMelodyType::ABABCadence));
+    /// assert_eq!("ABAB'|_,1".parse(), Ok(SynthwaveMelodyType::ABABCadence));
+    ///
+    /// let custom = "AAB'A".parse::<SynthwaveMelodyType>().unwrap();
+    /// assert_eq!(custom.form(), ['A', 'A', 'B', 'A']);
+    /// assert_eq!(custom.to_string(), "AAB'A");
+    ///
+    /// let planned = "AABA|2,3".parse::<SynthwaveMelodyType>().unwrap();
+    /// assert_eq!(planned.form(), ['A', 'A', 'B', 'A']);
+    /// assert_eq!(planned.cadences(), CadencePlan { half: Some(2), full: Some(3) });
+    /// assert_eq!(planned.to_string(), "AABA|2,3");
+    ///
+    /// let mutated = "ABAB|_,1".parse::<SynthwaveMelodyType>().unwrap();
+    /// assert_eq!(mutated, SynthwaveMelodyType::Mutated(MutatedForm::ABAB, CadencePlan::AUTHENTIC));
+    /// assert_eq!(mutated.form(), ['A', 'B', 'A', 'B']);
+    /// assert_eq!(mutated.to_string(), "ABAB|_,1")
+    /// ```
+
+    #[inline]
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, cadences) = match s.trim().split_once('|') {
+            Some((name, cadences)) => (name.trim(), Some(parse_cadences(cadences)?)),
+            None => (s.trim(), None),
         };
 
-        if is_without_three_times_repetition(&lead)
-            && is_distance_between_notes_not_big(&lead)
-            && is_not_too_big_parts(&lead)
-            && is_not_bad_rhythm_with_odd_notes(&lead)
-            && is_at_least_one_zero_delay(&lead)
-        {
-            Some(lead)
-        } else {
-            None
+        let named = melody_types().into_iter().find(|melody_type| {
+            name.eq_ignore_ascii_case(&melody_type.to_string())
+                || name.eq_ignore_ascii_case(&format!("{:?}", melody_type))
+        });
+
+        match (named, cadences) {
+            (Some(melody_type), None) => Ok(melody_type),
+
+            (Some(melody_type), Some(cadences)) if cadences == melody_type.cadences() => {
+                Ok(melody_type)
+            }
+
+            (Some(melody_type), Some(cadences)) => Ok(match melody_type {
+                SynthwaveMelodyType::ABAB => {
+                    SynthwaveMelodyType::Mutated(MutatedForm::ABAB, cadences)
+                }
+                SynthwaveMelodyType::AAAB => {
+                    SynthwaveMelodyType::Mutated(MutatedForm::AAAB, cadences)
+                }
+                SynthwaveMelodyType::ABAC => {
+                    SynthwaveMelodyType::Mutated(MutatedForm::ABAC, cadences)
+                }
+                _ => SynthwaveMelodyType::Custom(melody_type.phrases().with_cadences(cadences)),
+            }),
+
+            (None, _) => s.parse().map(SynthwaveMelodyType::Custom),
+        }
+    }
+}
+
+/// Forms with their own generation, where B and C are mutations of A.
+/// With the other cadence plan the same material is generated,
+/// only the phrase endings differ
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     melody_type::SynthwaveMelodyType,
+///     midi::{scale_notes, time_signature::TimeSignature},
+///     rules::RuleSet,
+/// };
+/// use rand::{rngs::StdRng, SeedableRng};
+/// use rust_music_theory::{note::PitchClass, scale::{Mode, ScaleType}};
+///
+/// let notes = scale_notes(PitchClass::C, ScaleType::Diatonic, Mode::Aeolian, 4..=5).unwrap();
+///
+/// let generate = |melody_type: &str| {
+///     melody_type
+///         .parse::<SynthwaveMelodyType>()
+///         .unwrap()
+///         .generate_synthwave_melody_with_diagnostics(
+///             PitchClass::C,
+///             &notes,
+///             TimeSignature::COMMON,
+///             &RuleSet::new(),
+///             1,
+///             &mut StdRng::seed_from_u64(42),
+///         )
+///         .unwrap()
+///         .lead
+/// };
+///
+/// let (abab, planned) = (generate("ABAB"), generate("ABAB|_,1"));
+///
+/// // Only the half cadence at the end of the 4-th bar is skipped
+/// assert_eq!(abab.len(), planned.len());
+/// assert!(abab.iter().zip(&planned).all(|(a, b)| a.start() == b.start()));
+/// assert!(abab.iter().zip(&planned).filter(|(a, b)| a.note() != b.note()).count() <= 1)
+/// ```
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MutatedForm {
+    ABAB,
+    AAAB,
+    ABAC,
+}
+
+impl From<MutatedForm> for SynthwaveMelodyType {
+    #[inline]
+    fn from(form: MutatedForm) -> Self {
+        match form {
+            MutatedForm::ABAB => SynthwaveMelodyType::ABAB,
+            MutatedForm::AAAB => SynthwaveMelodyType::AAAB,
+            MutatedForm::ABAC => SynthwaveMelodyType::ABAC,
+        }
+    }
+}
+
+/// How the phrase is derived from the material of its letter
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum PhraseVariation {
+    /// Material of the letter as it is
+    Original,
+
+    /// Mutated material with the last note resolved to the tonic, written as `'`
+    Cadence,
+
+    /// Material developed with the transform, written as
+    /// `+` / `-` (sequence a step up / down, repeated for more steps),
+    /// `~` (inversion), `<` (retrograde), `*` (augmentation),
+    /// `/` (diminution), `^` (ornamentation) or `.` (truncation)
+    Transformed(PhraseTransform),
+}
+
+impl PhraseVariation {
+    /// Variation written with the given mark
+
+    #[inline]
+    fn from_mark(mark: char) -> Option<Self> {
+        match mark {
+            '\'' => Some(PhraseVariation::Cadence),
+            _ => TRANSFORM_MARKS
+                .iter()
+                .find(|(transform_mark, _)| *transform_mark == mark)
+                .map(|&(_, transform)| PhraseVariation::Transformed(transform)),
+        }
+    }
+
+    /// Adds one more mark after the letter.
+    /// Only sequences can be repeated to move by more steps
+
+    #[inline]
+    fn with_mark(&self, mark: char) -> Option<Self> {
+        use PhraseTransform::Sequence;
+
+        match (self, PhraseVariation::from_mark(mark)?) {
+            (PhraseVariation::Original, variation) => Some(variation),
+
+            (
+                PhraseVariation::Transformed(Sequence(steps)),
+                PhraseVariation::Transformed(Sequence(step)),
+            ) if steps.signum() == step => steps
+                .checked_add(step)
+                .map(|steps| PhraseVariation::Transformed(Sequence(steps))),
+
+            _ => None,
+        }
+    }
+}
+
+impl Display for PhraseVariation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhraseVariation::Original => Ok(()),
+            PhraseVariation::Cadence => write!(f, "'"),
+
+            PhraseVariation::Transformed(PhraseTransform::Sequence(steps)) => {
+                let mark = if *steps < 0 { '-' } else { '+' };
+                write!(
+                    f,
+                    "{}",
+                    mark.to_string().repeat(steps.unsigned_abs() as usize)
+                )
+            }
+
+            PhraseVariation::Transformed(transform) => TRANSFORM_MARKS
+                .iter()
+                .find(|(_, marked)| marked == transform)
+                .map(|(mark, _)| write!(f, "{mark}"))
+                .unwrap_or(Ok(())),
+        }
+    }
+}
+
+/// Single bar of the form: letter of the material and its variation
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct FormPhrase {
+    pub letter: char,
+    pub variation: PhraseVariation,
+}
+
+impl FormPhrase {
+    #[inline]
+    pub fn new(letter: char, variation: PhraseVariation) -> Self {
+        Self { letter, variation }
+    }
+}
+
+/// Form of the 4 bar phrase, one phrase per bar.
+/// First occurrence of the letter is a newly generated phrase,
+/// next ones repeat it. Letter may be followed by the mark of the variation
+/// (see [PhraseVariation]): `'` - mutation that resolves to the tonic,
+/// `+` / `-` - a scale step up / down, `~` - inversion, `<` - retrograde,
+/// `*` - augmentation, `/` - diminution, `^` - ornamentation, `.` - truncation
+/// (e.g. `AAB'A`, `AA+BA-`, `AB~A<B.`).
+/// Form may end with the degrees of the half and the full cadences
+/// after `|` ([CadencePlan]), `_` stands for no cadence
+/// (e.g. `ABAC|_,1`). Half cadence on the 5-th degree
+/// and the authentic one on the tonic are used by default
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     melody_type::{MelodyForm, PhraseVariation},
+///     midi::generator::{cadence::CadencePlan, phrase::PhraseTransform},
+/// };
+///
+/// let form = "AA++B~A<".parse::<MelodyForm>().unwrap();
+///
+/// assert_eq!(
+///     form.phrases[1].variation,
+///     PhraseVariation::Transformed(PhraseTransform::Sequence(2))
+/// );
+/// assert_eq!(
+///     form.phrases[3].variation,
+///     PhraseVariation::Transformed(PhraseTransform::Retrograde)
+/// );
+/// assert_eq!(form.to_string(), "AA++B~A<");
+/// assert!("AB~<AB".parse::<MelodyForm>().is_err());
+///
+/// let planned = "ABAC|_,1".parse::<MelodyForm>().unwrap();
+/// assert_eq!(planned.cadences, CadencePlan::AUTHENTIC);
+/// assert_eq!(planned.to_string(), "ABAC|_,1");
+/// assert!("ABAC|5".parse::<MelodyForm>().is_err())
+/// ```
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct MelodyForm {
+    pub phrases: [FormPhrase; 4],
+
+    /// Degrees that the phrases end on
+    pub cadences: CadencePlan,
+}
+
+impl MelodyForm {
+    /// Constructs form with the half cadence in the 4-th bar
+    /// and the authentic one in the 8-th bar
+
+    #[inline]
+    pub fn new(phrases: [FormPhrase; 4]) -> Self {
+        Self {
+            phrases,
+            cadences: CadencePlan::default(),
+        }
+    }
+
+    /// Replaces degrees that the phrases end on
+
+    #[inline]
+    pub fn with_cadences(mut self, cadences: CadencePlan) -> Self {
+        self.cadences = cadences;
+        self
+    }
+
+    /// Letters of the phrases without the variations
+
+    #[inline]
+    pub fn letters(&self) -> [char; 4] {
+        self.phrases.map(|phrase| phrase.letter)
+    }
+}
+
+impl FromStr for MelodyForm {
+    type Err = MelodyFormParseError;
+
+    /// Parses form written with letters and marks of the variations
+    /// and the optional cadence plan (e.g. `AAB'A`, `ABAC|_,1`).
+    /// Letters are case-insensitive
+
+    #[inline]
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (phrases, cadences) = match s.trim().split_once('|') {
+            Some((phrases, cadences)) => (phrases, parse_cadences(cadences)?),
+            None => (s.trim(), CadencePlan::default()),
+        };
+
+        let phrases = phrases
+            .trim()
+            .chars()
+            .try_fold(Vec::new(), |mut phrases, symbol| {
+                if symbol.is_ascii_alphabetic() {
+                    phrases.push(FormPhrase::new(
+                        symbol.to_ascii_uppercase(),
+                        PhraseVariation::Original,
+                    ));
+
+                    return Ok(phrases);
+                }
+
+                if PhraseVariation::from_mark(symbol).is_none() {
+                    return Err(MelodyFormParseError::UnknownSymbol(symbol));
+                }
+
+                let phrase = phrases
+                    .last_mut()
+                    .ok_or(MelodyFormParseError::MisplacedMark(symbol))?;
+
+                phrase.variation = phrase
+                    .variation
+                    .with_mark(symbol)
+                    .ok_or(MelodyFormParseError::MisplacedMark(symbol))?;
+
+                Ok(phrases)
+            })?;
+
+        let phrases_num = phrases.len();
+
+        phrases
+            .try_into()
+            .map(|phrases| MelodyForm::new(phrases).with_cadences(cadences))
+            .map_err(|_| MelodyFormParseError::InvalidLength(phrases_num))
+    }
+}
+
+impl Display for MelodyForm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.phrases
+            .iter()
+            .try_for_each(|phrase| write!(f, "{}{}", phrase.letter, phrase.variation))?;
+
+        match self.cadences == CadencePlan::default() {
+            true => Ok(()),
+            false => write!(f, "|{}", self.cadences),
+        }
+    }
+}
+
+/// Parses cadence plan written after the form
+
+#[inline]
+fn parse_cadences(s: &str) -> std::result::Result<CadencePlan, MelodyFormParseError> {
+    s.parse()
+        .map_err(|_| MelodyFormParseError::InvalidCadences(s.trim().to_string()))
+}
+
+/// Errors that may occur while parsing the melody form
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MelodyFormParseError {
+    /// Form does not consist of 4 phrases
+    InvalidLength(usize),
+
+    /// Symbol is neither a letter nor a mark of the variation
+    UnknownSymbol(char),
+
+    /// Mark of the variation does not follow the letter
+    /// or follows another mark (except repeated `+` or `-`)
+    MisplacedMark(char),
+
+    /// Cadence plan after `|` is not two degrees
+    /// (1..=7 or `_`) separated with the comma
+    InvalidCadences(String),
+}
+
+impl Display for MelodyFormParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MelodyFormParseError::InvalidLength(phrases) => {
+                write!(f, "Form has {phrases} phrases, expected 4 (one per bar)")
+            }
+
+            MelodyFormParseError::UnknownSymbol(symbol) => write!(
+                f,
+                "Unknown symbol '{symbol}'. Expected letters and marks ', +, -, ~, <, *, /, ^, ."
+            ),
+
+            MelodyFormParseError::MisplacedMark(mark) => {
+                write!(
+                    f,
+                    "Mark '{mark}' has to follow the letter or the same sequence mark"
+                )
+            }
+
+            MelodyFormParseError::InvalidCadences(cadences) => write!(
+                f,
+                "Invalid cadences '{cadences}'. Expected degrees 1..=7 or _ separated with the comma (e.g. 5,1)"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MelodyFormParseError {}
+
+/// Outcome of the melody generation with the rules
+
+#[derive(Clone, Debug)]
+pub struct MelodyGeneration {
+    /// Melody that satisfies all rules,
+    /// or the closest candidate if the attempts are spent
+    pub lead: Vec<NoteData>,
+
+    /// Number of generated candidates
+    pub attempts: usize,
+
+    /// Number of rejected candidates per violated rule.
+    /// Candidate is counted for every rule it violates
+    pub rejections: BTreeMap<String, usize>,
+
+    /// Rules that are violated by the resulting melody
+    pub report: RuleReport,
+}
+
+impl MelodyGeneration {
+    /// Rejections per rule in form `rule: count`, most frequent first
+
+    #[inline]
+    pub fn rejections_summary(&self) -> String {
+        self.rejections
+            .iter()
+            .sorted_by(|(_, a), (_, b)| b.cmp(a))
+            .map(|(rule, count)| format!("{rule}: {count}"))
+            .join(", ")
+    }
+
+    /// Description of the violated rules
+    /// if no candidate has satisfied all of them
+
+    #[inline]
+    pub fn warning(&self) -> Option<String> {
+        match self.report.is_satisfied() {
+            true => None,
+            false => Some(format!(
+                "no melody satisfies all rules in {} attempts ({}), using the closest one: {}",
+                self.attempts,
+                self.rejections_summary(),
+                self.report
+            )),
         }
     }
 }
@@ -63,12 +814,20 @@ fn generate_abab_melody(
     key: PitchClass,
     scale_notes: &Vec<Note>,
     melody_length: usize,
-) -> Vec<NoteData> {
-    let a_melody = generate_lead_melody_with_bpm_and_len(key, scale_notes, melody_length);
-    let b_melody = mutate(a_melody.clone(), scale_notes, 0.75);
+    time_signature: TimeSignature,
+    rng: &mut impl Rng,
+) -> Result<Vec<NoteData>> {
+    let a_melody = generate_lead_melody_with_bpm_and_len(
+        key,
+        scale_notes,
+        melody_length,
+        time_signature,
+        rng,
+    )?;
+    let b_melody = mutate(a_melody.clone(), scale_notes, PHRASE_MUTATION_RATE, rng);
 
-    let a_delay = time_before_bar_end(*a_melody.last().unwrap());
-    let b_delay = time_before_bar_end(*b_melody.last().unwrap());
+    let a_delay = time_before_bar_end(&a_melody, time_signature);
+    let b_delay = time_before_bar_end(&b_melody, time_signature);
 
     let mut second_part = b_melody.clone();
     let first_note = second_part[0];
@@ -89,7 +848,7 @@ fn generate_abab_melody(
     let first_note = bar_8[0];
     bar_8[0] = first_note.clone_with_new_delay(b_delay);
 
-    vec![bar_4, bar_8].into_iter().flatten().collect()
+    Ok(vec![bar_4, bar_8].into_iter().flatten().collect())
 }
 
 #[inline]
@@ -97,12 +856,26 @@ fn generate_aaab_melody(
     key: PitchClass,
     scale_notes: &Vec<Note>,
     melody_length: usize,
-) -> Vec<NoteData> {
-    let a_melody = generate_lead_melody_with_bpm_and_len(key, scale_notes, melody_length);
-    let b_melody = generate_lead_melody_with_bpm_and_len(key, scale_notes, melody_length);
+    time_signature: TimeSignature,
+    rng: &mut impl Rng,
+) -> Result<Vec<NoteData>> {
+    let a_melody = generate_lead_melody_with_bpm_and_len(
+        key,
+        scale_notes,
+        melody_length,
+        time_signature,
+        rng,
+    )?;
+    let b_melody = generate_lead_melody_with_bpm_and_len(
+        key,
+        scale_notes,
+        melody_length,
+        time_signature,
+        rng,
+    )?;
 
-    let a_delay = time_before_bar_end(*a_melody.last().unwrap());
-    let b_delay = time_before_bar_end(*b_melody.last().unwrap());
+    let a_delay = time_before_bar_end(&a_melody, time_signature);
+    let b_delay = time_before_bar_end(&b_melody, time_signature);
 
     let mut second_part = a_melody.clone();
     let first_note = second_part[0];
@@ -123,7 +896,7 @@ fn generate_aaab_melody(
     let first_note = bar_8[0];
     bar_8[0] = first_note.clone_with_new_delay(b_delay);
 
-    vec![bar_4, bar_8].into_iter().flatten().collect()
+    Ok(vec![bar_4, bar_8].into_iter().flatten().collect())
 }
 
 #[inline]
@@ -131,14 +904,28 @@ fn generate_abac_melody(
     key: PitchClass,
     scale_notes: &Vec<Note>,
     melody_length: usize,
-) -> Vec<NoteData> {
-    let a_melody = generate_lead_melody_with_bpm_and_len(key, scale_notes, melody_length);
-    let b_melody = generate_lead_melody_with_bpm_and_len(key, scale_notes, melody_length);
-    let c_melody = mutate(a_melody.clone(), scale_notes, 0.75);
+    time_signature: TimeSignature,
+    rng: &mut impl Rng,
+) -> Result<Vec<NoteData>> {
+    let a_melody = generate_lead_melody_with_bpm_and_len(
+        key,
+        scale_notes,
+        melody_length,
+        time_signature,
+        rng,
+    )?;
+    let b_melody = generate_lead_melody_with_bpm_and_len(
+        key,
+        scale_notes,
+        melody_length,
+        time_signature,
+        rng,
+    )?;
+    let c_melody = mutate(a_melody.clone(), scale_notes, PHRASE_MUTATION_RATE, rng);
 
-    let a_delay = time_before_bar_end(*a_melody.last().unwrap());
-    let b_delay = time_before_bar_end(*b_melody.last().unwrap());
-    let c_delay = time_before_bar_end(*c_melody.last().unwrap());
+    let a_delay = time_before_bar_end(&a_melody, time_signature);
+    let b_delay = time_before_bar_end(&b_melody, time_signature);
+    let c_delay = time_before_bar_end(&c_melody, time_signature);
 
     let mut second_part = b_melody.clone();
     let first_note = second_part[0];
@@ -161,10 +948,108 @@ fn generate_abac_melody(
     let first_note = bar_8[0];
     bar_8[0] = first_note.clone_with_new_delay(c_delay);
 
-    vec![bar_4, bar_8].into_iter().flatten().collect()
+    Ok(vec![bar_4, bar_8].into_iter().flatten().collect())
+}
+
+/// Generates phrases of the form from the letters' material,
+/// then joins them and duplicates 4 bars to 8 bars.
+/// Material of every letter is regenerated (at most [PHRASE_ATTEMPTS] times)
+/// until it satisfies the local rules ([RuleSet::local]) on its own,
+/// so forms with many new phrases are still likely to pass them.
+/// The whole lead is checked with all rules by the caller
+
+#[inline]
+fn generate_form_melody(
+    key: PitchClass,
+    scale_notes: &Vec<Note>,
+    melody_length: usize,
+    time_signature: TimeSignature,
+    form: &MelodyForm,
+    rules: &RuleSet,
+    rng: &mut impl Rng,
+) -> Result<Vec<NoteData>> {
+    let phrase_rules = rules.local();
+    let mut materials = HashMap::new();
+    let mut bars = Vec::with_capacity(form.phrases.len());
+
+    for phrase in form.phrases {
+        let material = match materials.entry(phrase.letter) {
+            Entry::Occupied(material) => material.into_mut(),
+            Entry::Vacant(entry) => entry.insert(generate_phrase(
+                key,
+                scale_notes,
+                melody_length,
+                time_signature,
+                &phrase_rules,
+                rng,
+            )?),
+        };
+
+        let bar = match phrase.variation {
+            PhraseVariation::Original => material.clone(),
+
+            PhraseVariation::Cadence => resolve_to_tonic(
+                mutate(material.clone(), scale_notes, PHRASE_MUTATION_RATE, rng),
+                key,
+                scale_notes,
+            ),
+
+            PhraseVariation::Transformed(transform) => {
+                transform.apply(material.clone(), scale_notes, time_signature, rng)
+            }
+        };
+
+        bars.push(bar);
+    }
+
+    // The 4 bars are repeated, so the 5-th bar is delayed
+    // by the silence at the end of the 4-th one
+
+    Ok(join_bars([bars.clone(), bars].concat(), time_signature))
+}
+
+/// Generates single bar phrase that satisfies the given local rules.
+/// The last attempt is used if none does
+
+#[inline]
+fn generate_phrase(
+    key: PitchClass,
+    scale_notes: &Vec<Note>,
+    melody_length: usize,
+    time_signature: TimeSignature,
+    rules: &RuleSet,
+    rng: &mut impl Rng,
+) -> Result<Vec<NoteData>> {
+    let mut phrase = Vec::new();
+
+    for _ in 0..PHRASE_ATTEMPTS {
+        phrase = generate_lead_melody_with_bpm_and_len(
+            key,
+            scale_notes,
+            melody_length,
+            time_signature,
+            rng,
+        )?;
+
+        if rules.is_satisfied(&phrase) {
+            break;
+        }
+    }
+
+    Ok(phrase)
 }
 
+/// Changes the last note's pitch to the closest tonic of the scale
+
 #[inline]
-fn time_before_bar_end(last_note: NoteData) -> DeltaTime {
-    512 - last_note.start() - last_note.length()
+fn resolve_to_tonic(
+    mut phrase: Vec<NoteData>,
+    key: PitchClass,
+    scale_notes: &Vec<Note>,
+) -> Vec<NoteData> {
+    if let Some(last) = phrase.last_mut() {
+        *last = resolve_to_pitch_class(*last, key.into_u8(), scale_notes);
+    }
+
+    phrase
 }
@@ -1,3 +1,4 @@
+use crate::midi::time_signature::TimeSignature;
 use std::{fmt::Display, time::Duration};
 
 /// BPM trait to count tempo and bar time.
@@ -9,7 +10,15 @@ pub trait BPM: Clone + Copy + Eq + Ord + Sized + Display {
 
     #[inline]
     fn bar_time(&self) -> Duration {
-        Duration::from_millis((self.tempo() as f64 / 250.0).round() as u64)
+        self.bar_time_in(TimeSignature::COMMON)
+    }
+
+    /// Time of the bar with the given time signature
+
+    #[inline]
+    fn bar_time_in(&self, time_signature: TimeSignature) -> Duration {
+        let quarters = time_signature.steps_per_bar() as f64 / 4.0;
+        Duration::from_millis((self.tempo() as f64 * quarters / 1000.0).round() as u64)
     }
 
     #[inline]
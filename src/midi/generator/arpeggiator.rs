@@ -0,0 +1,215 @@
+use crate::{
+    midi::generator::{
+        arpeggio_types::ArpeggioTypes, get_bar_ratio, harmony::scale_degrees,
+        with_delays_from_starts,
+    },
+    notes::{note::Note, note_data::*, ChordData},
+};
+
+use itertools::Itertools;
+use rand::Rng;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+/// Velocity of the arpeggio notes that start on the beat
+const ACCENT_VELOCITY: Velocity = 80;
+
+/// Velocity of all other arpeggio notes
+const ARPEGGIO_VELOCITY: Velocity = 65;
+
+/// Rate (length of a single note) of the arpeggio
+
+#[derive(EnumIter, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ArpeggioRate {
+    Eighth,
+    Sixteenth,
+    EighthTriplet,
+    SixteenthTriplet,
+}
+
+impl ArpeggioRate {
+    /// Generates random rate
+
+    #[inline]
+    pub fn random_rate(rng: &mut impl Rng) -> Self {
+        match rng.gen_range(0..4) {
+            0 => ArpeggioRate::Eighth,
+            1 => ArpeggioRate::Sixteenth,
+            2 => ArpeggioRate::EighthTriplet,
+            _ => ArpeggioRate::SixteenthTriplet,
+        }
+    }
+
+    /// Number of notes played during a single beat
+
+    #[inline]
+    pub fn notes_per_beat(&self) -> u32 {
+        match self {
+            ArpeggioRate::Eighth => 2,
+            ArpeggioRate::Sixteenth => 4,
+            ArpeggioRate::EighthTriplet => 3,
+            ArpeggioRate::SixteenthTriplet => 6,
+        }
+    }
+}
+
+/// Order in which notes of the chord are played.
+/// [ArpeggioPattern::Typed] applies [ArpeggioTypes]
+/// to every chord note, producing two notes per chord note
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ArpeggioPattern {
+    Up,
+    Down,
+    UpDown,
+    Random,
+    Typed(ArpeggioTypes),
+}
+
+impl ArpeggioPattern {
+    /// Generates random pattern.
+    /// If [ArpeggioPattern::Typed] is chosen,
+    /// arpeggio type is also random
+
+    #[inline]
+    pub fn random_pattern(rng: &mut impl Rng) -> Self {
+        match rng.gen_range(0..5) {
+            0 => ArpeggioPattern::Up,
+            1 => ArpeggioPattern::Down,
+            2 => ArpeggioPattern::UpDown,
+            3 => ArpeggioPattern::Random,
+            _ => ArpeggioPattern::Typed(ArpeggioTypes::random_arp(rng)),
+        }
+    }
+}
+
+/// Generates arpeggio from the given chords.
+/// Every chord is played note by note with the given rate
+/// for the whole chord's length. Chord notes are repeated
+/// in the next octaves according to the octave span
+/// (1 - only chord notes, 2 - chord notes and an octave above, etc.).
+/// Produced notes have absolute start time
+/// and the delay from the end of the previous note
+
+#[inline]
+pub fn generate_arpeggio(
+    harmony: &Vec<ChordData>,
+    scale_notes: &Vec<Note>,
+    rate: ArpeggioRate,
+    pattern: ArpeggioPattern,
+    octave_span: u32,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
+    let arpeggio_scale = scale_in_all_octaves(scale_notes);
+    let beat_time = get_bar_ratio(4);
+
+    let arpeggio = harmony
+        .iter()
+        .filter_map(|chord| {
+            let start = chord.iter().map(NoteData::start).min()?;
+            let length = chord.iter().map(NoteData::length).max()?;
+            Some((chord, start, length))
+        })
+        .flat_map(|(chord, start, length)| {
+            let steps = length * rate.notes_per_beat() / beat_time;
+
+            let pitches = chord_pool(chord, octave_span);
+            let pitches = pattern_sequence(&pitches, &arpeggio_scale, pattern, steps, rng);
+
+            // Triplets do not divide beat evenly,
+            // so every onset is calculated from the chord's start
+
+            let onset = |step: u32| start + step * beat_time / rate.notes_per_beat();
+
+            pitches
+                .into_iter()
+                .enumerate()
+                .map(|(step, note)| {
+                    let step = step as u32;
+
+                    let velocity = match step % rate.notes_per_beat() {
+                        0 => ACCENT_VELOCITY,
+                        _ => ARPEGGIO_VELOCITY,
+                    };
+
+                    let note_start = onset(step);
+                    NoteData::new(note, velocity, note_start, onset(step + 1) - note_start, 0)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    with_delays_from_starts(arpeggio)
+}
+
+/// Constructs all notes of the chord
+/// in the given number of octaves, sorted by pitch
+
+#[inline]
+fn chord_pool(chord: &ChordData, octave_span: u32) -> Vec<Note> {
+    (0..octave_span.max(1) as u8)
+        .flat_map(|octave| chord.iter().filter_map(move |nd| nd.note().up(octave * 12)))
+        .sorted()
+        .dedup()
+        .collect()
+}
+
+/// Constructs sequence of the given length from the pool of notes
+
+#[inline]
+fn pattern_sequence(
+    pool: &Vec<Note>,
+    arpeggio_scale: &Vec<Note>,
+    pattern: ArpeggioPattern,
+    steps: u32,
+    rng: &mut impl Rng,
+) -> Vec<Note> {
+    if pool.is_empty() {
+        return Vec::new();
+    }
+
+    let cycle = match pattern {
+        ArpeggioPattern::Up => pool.clone(),
+        ArpeggioPattern::Down => pool.iter().rev().copied().collect(),
+
+        ArpeggioPattern::UpDown => pool
+            .iter()
+            .chain(pool.iter().rev().skip(1).take(pool.len().saturating_sub(2)))
+            .copied()
+            .collect(),
+
+        ArpeggioPattern::Random => (0..steps)
+            .map(|_| pool[rng.gen_range(0..pool.len())])
+            .collect(),
+
+        ArpeggioPattern::Typed(arpeggio_type) => pool
+            .iter()
+            .flat_map(|&tonic| {
+                let tonic = NoteData::new(tonic, ARPEGGIO_VELOCITY, 0, 0, 0);
+
+                arpeggio_type
+                    .notes_from_tonic(tonic, arpeggio_scale)
+                    .unwrap_or_else(|| vec![tonic; 2])
+                    .into_iter()
+                    .map(|nd| nd.note())
+            })
+            .collect(),
+    };
+
+    cycle.into_iter().cycle().take(steps as usize).collect()
+}
+
+/// Constructs all notes that have the same
+/// pitch classes as the notes of the scale
+
+#[inline]
+fn scale_in_all_octaves(scale_notes: &Vec<Note>) -> Vec<Note> {
+    let degrees = scale_degrees(scale_notes)
+        .into_iter()
+        .map(|note| note.midi() % 12)
+        .collect::<Vec<_>>();
+
+    Note::iter()
+        .filter(|note| degrees.contains(&(note.midi() % 12)))
+        .collect()
+}
@@ -1,6 +1,5 @@
 use crate::notes::{note::Note, note_data::NoteData};
 use rand::Rng;
-use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
 #[derive(EnumIter, Copy, Clone, Eq, PartialEq, Debug)]
@@ -18,10 +17,16 @@ impl ArpeggioTypes {
     /// Generates random arpeggio
 
     #[inline]
-    pub fn random_arp() -> Self {
-        let mut rng = rand::thread_rng();
-        let index = rng.gen_range(0..=6);
-        Self::iter().skip(index).next().unwrap()
+    pub fn random_arp(rng: &mut impl Rng) -> Self {
+        match rng.gen_range(0..=6) {
+            0 => ArpeggioTypes::SameSame,
+            1 => ArpeggioTypes::SameUp,
+            2 => ArpeggioTypes::SameDown,
+            3 => ArpeggioTypes::UpSame,
+            4 => ArpeggioTypes::UpUp,
+            5 => ArpeggioTypes::DownSame,
+            _ => ArpeggioTypes::DownDown,
+        }
     }
 
     /// Constructs notes based on tonic
@@ -0,0 +1,656 @@
+use crate::{
+    genetic::mutation::mutate,
+    melody_type::SynthwaveMelodyType,
+    midi::generator::{
+        arpeggiator::{generate_arpeggio, ArpeggioPattern, ArpeggioRate},
+        bass::{generate_bass, BassPattern},
+        drums::{generate_drums, DrumPattern},
+        harmony::{generate_harmony, ChordProgression, DEFAULT_CHORD_RANGE},
+    },
+    midi::time_signature::TimeSignature,
+    notes::{note::Note, note_data::*},
+};
+
+use rand::Rng;
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+/// Mutation rate of the lead material played in the bridge
+const BRIDGE_MUTATION_RATE: f32 = 0.5;
+
+/// Number of chords by which the progression
+/// is shifted in the bridge (e.g. I–V–vi–IV turns into vi–IV–I–V)
+const BRIDGE_PROGRESSION_SHIFT: u32 = 2;
+
+/// Kind of the song's section.
+/// Kind decides which lead material is played in the section
+/// and which instruments are played by default
+
+#[derive(EnumIter, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SectionKind {
+    /// Repeats the first part (A) of the lead
+    Intro,
+
+    /// Plays the whole lead loop
+    Verse,
+
+    /// Plays the whole lead loop doubled an octave higher
+    Chorus,
+
+    /// Plays mutated parts of the lead other than A
+    /// over the shifted progression
+    Bridge,
+
+    /// Repeats the first part (A) of the lead
+    Outro,
+}
+
+impl SectionKind {
+    /// Name of the section, used in markers and in the arrangement string
+    ///
+    /// # Example
+    /// ```
+    /// use music_generator::midi::generator::arrangement::SectionKind;
+    /// assert_eq!(SectionKind::Chorus.name(), "chorus")
+    /// ```
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            SectionKind::Intro => "intro",
+            SectionKind::Verse => "verse",
+            SectionKind::Chorus => "chorus",
+            SectionKind::Bridge => "bridge",
+            SectionKind::Outro => "outro",
+        }
+    }
+
+    /// Instruments that are played in the section
+    /// if they are not specified explicitly
+
+    #[inline]
+    pub fn default_instruments(&self) -> Instruments {
+        match self {
+            SectionKind::Intro | SectionKind::Outro => Instruments {
+                harmony: true,
+                arpeggio: true,
+                ..Instruments::default()
+            },
+
+            SectionKind::Verse => Instruments {
+                lead: true,
+                harmony: true,
+                bass: true,
+                drums: true,
+                ..Instruments::default()
+            },
+
+            SectionKind::Chorus => Instruments::all(),
+
+            SectionKind::Bridge => Instruments {
+                harmony: true,
+                arpeggio: true,
+                bass: true,
+                ..Instruments::default()
+            },
+        }
+    }
+}
+
+/// Toggles of the instruments (tracks) played in the section.
+/// In the arrangement string every instrument is written as a single letter:
+/// `l` - lead, `h` - harmony, `a` - arpeggio, `b` - bass, `d` - drums
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Instruments {
+    pub lead: bool,
+    pub harmony: bool,
+    pub arpeggio: bool,
+    pub bass: bool,
+    pub drums: bool,
+}
+
+impl Instruments {
+    /// All instruments are played
+
+    #[inline]
+    pub fn all() -> Self {
+        Self {
+            lead: true,
+            harmony: true,
+            arpeggio: true,
+            bass: true,
+            drums: true,
+        }
+    }
+
+    /// Letters of the instruments with their toggles
+
+    #[inline]
+    fn letters(&self) -> [(char, bool); 5] {
+        [
+            ('l', self.lead),
+            ('h', self.harmony),
+            ('a', self.arpeggio),
+            ('b', self.bass),
+            ('d', self.drums),
+        ]
+    }
+}
+
+impl FromStr for Instruments {
+    type Err = ArrangementParseError;
+
+    /// Parses instruments from letters (e.g. `lhbd`)
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.chars().try_fold(Self::default(), |instruments, letter| {
+            let mut instruments = instruments;
+
+            match letter.to_ascii_lowercase() {
+                'l' => instruments.lead = true,
+                'h' => instruments.harmony = true,
+                'a' => instruments.arpeggio = true,
+                'b' => instruments.bass = true,
+                'd' => instruments.drums = true,
+                _ => return Err(ArrangementParseError::UnknownInstrument(letter)),
+            }
+
+            Ok(instruments)
+        })
+    }
+}
+
+impl Display for Instruments {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.letters()
+            .into_iter()
+            .filter(|(_, is_played)| *is_played)
+            .try_for_each(|(letter, _)| write!(f, "{letter}"))
+    }
+}
+
+/// Single section of the song with its length in bars
+/// and the instruments that are played in it
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub bars: u32,
+    pub instruments: Instruments,
+}
+
+impl Section {
+    /// Creates section with the default instruments of its kind
+
+    #[inline]
+    pub fn new(kind: SectionKind, bars: u32) -> Self {
+        Self {
+            kind,
+            bars,
+            instruments: kind.default_instruments(),
+        }
+    }
+
+    /// Clones the section with the given instruments
+
+    #[inline]
+    pub fn with_instruments(self, instruments: Instruments) -> Self {
+        Self {
+            instruments,
+            ..self
+        }
+    }
+}
+
+impl FromStr for Section {
+    type Err = ArrangementParseError;
+
+    /// Parses section written as `kind[:bars[:instruments]]`
+    /// (e.g. `verse`, `verse:8` or `verse:8:lhbd`).
+    /// Missing length is 8 bars, missing instruments
+    /// are the default ones of the section's kind
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':');
+        let name = parts.next().unwrap_or_default();
+
+        let kind = SectionKind::iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| ArrangementParseError::UnknownSection(name.to_string()))?;
+
+        let bars = match parts.next() {
+            None => 8,
+            Some(bars) => bars
+                .parse::<u32>()
+                .ok()
+                .filter(|&bars| bars > 0)
+                .ok_or_else(|| ArrangementParseError::InvalidBars(bars.to_string()))?,
+        };
+
+        let section = Section::new(kind, bars);
+
+        match parts.next() {
+            None => Ok(section),
+            Some(instruments) => Ok(section.with_instruments(instruments.parse()?)),
+        }
+    }
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.kind.name(), self.bars, self.instruments)
+    }
+}
+
+/// Sequence of sections that forms the whole song.
+/// Can be parsed from the comma separated sections
+/// (e.g. `intro:4,verse:8:lhbd,chorus:8,outro:4`, see [Section])
+///
+/// # Example
+/// ```
+/// use music_generator::midi::generator::arrangement::Arrangement;
+///
+/// let arrangement = "intro:4,verse,chorus:8:lhd".parse::<Arrangement>().unwrap();
+/// assert_eq!(arrangement.bars(), 20);
+/// assert_eq!(arrangement.to_string(), "intro:4:ha,verse:8:lhbd,chorus:8:lhd")
+/// ```
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Arrangement {
+    sections: Vec<Section>,
+}
+
+impl Arrangement {
+    /// Creates arrangement from the given sections.
+    /// Returns [None] if there are no sections or some of them are empty
+
+    #[inline]
+    pub fn new(sections: Vec<Section>) -> Option<Self> {
+        match sections.is_empty() || sections.iter().any(|section| section.bars == 0) {
+            true => None,
+            false => Some(Self { sections }),
+        }
+    }
+
+    /// Gets all sections of the arrangement
+
+    #[inline]
+    pub fn sections(&self) -> &Vec<Section> {
+        &self.sections
+    }
+
+    /// Total number of bars in the arrangement
+
+    #[inline]
+    pub fn bars(&self) -> u32 {
+        self.sections.iter().map(|section| section.bars).sum()
+    }
+
+    /// Sections with their absolute start time
+
+    #[inline]
+    pub fn section_starts(&self, time_signature: TimeSignature) -> Vec<(DeltaTime, Section)> {
+        self.sections
+            .iter()
+            .scan(0, |start, &section| {
+                let section_start = *start;
+                *start += section.bars * time_signature.bar_time();
+                Some((section_start, section))
+            })
+            .collect()
+    }
+}
+
+impl Default for Arrangement {
+    /// Intro (4 bars), verse (8), chorus (8),
+    /// bridge (4), chorus (8) and outro (4)
+
+    #[inline]
+    fn default() -> Self {
+        Self {
+            sections: vec![
+                Section::new(SectionKind::Intro, 4),
+                Section::new(SectionKind::Verse, 8),
+                Section::new(SectionKind::Chorus, 8),
+                Section::new(SectionKind::Bridge, 4),
+                Section::new(SectionKind::Chorus, 8),
+                Section::new(SectionKind::Outro, 4),
+            ],
+        }
+    }
+}
+
+impl FromStr for Arrangement {
+    type Err = ArrangementParseError;
+
+    /// Parses comma separated sections.
+    /// `default` stands for the [Arrangement::default]
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("default") {
+            return Ok(Self::default());
+        }
+
+        let sections = s
+            .split(',')
+            .map(Section::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::new(sections).ok_or(ArrangementParseError::Empty)
+    }
+}
+
+impl Display for Arrangement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sections = self
+            .sections
+            .iter()
+            .map(Section::to_string)
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", sections.join(","))
+    }
+}
+
+/// Errors that may occur while parsing the arrangement
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArrangementParseError {
+    /// Arrangement does not contain any section
+    Empty,
+
+    /// Section's name is not one of the [SectionKind]
+    UnknownSection(String),
+
+    /// Section's length is not a positive number
+    InvalidBars(String),
+
+    /// Instrument's letter is not one of `l`, `h`, `a`, `b`, `d`
+    UnknownInstrument(char),
+}
+
+impl Display for ArrangementParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArrangementParseError::Empty => write!(f, "Arrangement has no sections"),
+
+            ArrangementParseError::UnknownSection(name) => write!(
+                f,
+                "Unknown section '{name}'. Expected one of: {}",
+                SectionKind::iter()
+                    .map(|kind| kind.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+
+            ArrangementParseError::InvalidBars(bars) => write!(
+                f,
+                "Invalid number of bars '{bars}'. Expected a positive number"
+            ),
+
+            ArrangementParseError::UnknownInstrument(letter) => write!(
+                f,
+                "Unknown instrument '{letter}'. Expected letters: l, h, a, b, d"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArrangementParseError {}
+
+/// Settings of the accompanying instruments,
+/// shared by all sections of the song
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Accompaniment {
+    pub progression: ChordProgression,
+    pub arpeggio_rate: ArpeggioRate,
+    pub arpeggio_pattern: ArpeggioPattern,
+    pub arpeggio_octaves: u32,
+    pub bass_pattern: BassPattern,
+    pub drum_pattern: DrumPattern,
+}
+
+/// Notes of all tracks of the arranged song.
+/// All notes have absolute start time
+/// and have to be composed with the polyphonic composer
+
+#[derive(Clone, Debug, Default)]
+pub struct ArrangedTracks {
+    pub lead: Vec<NoteData>,
+    pub harmony: Vec<NoteData>,
+    pub arpeggio: Vec<NoteData>,
+    pub bass: Vec<NoteData>,
+    pub drums: Vec<NoteData>,
+}
+
+/// Lead loop with its settings, shared by all sections
+
+struct LeadMaterial<'a> {
+    melody_type: SynthwaveMelodyType,
+    lead: &'a Vec<NoteData>,
+    scale_notes: &'a Vec<Note>,
+    time_signature: TimeSignature,
+}
+
+/// Arranges the song from the generated lead loop.
+/// Every section reuses or varies the lead material
+/// from the parts of the melody type (A, B, C, see [SectionKind]).
+/// Harmony, arpeggio, bass and drums are generated
+/// for every section separately with the same settings.
+/// Only instruments that are toggled in the section are played
+
+#[inline]
+pub fn arrange_song(
+    arrangement: &Arrangement,
+    melody_type: SynthwaveMelodyType,
+    lead: &Vec<NoteData>,
+    scale_notes: &Vec<Note>,
+    time_signature: TimeSignature,
+    accompaniment: Accompaniment,
+    rng: &mut impl Rng,
+) -> ArrangedTracks {
+    let material = LeadMaterial {
+        melody_type,
+        lead,
+        scale_notes,
+        time_signature,
+    };
+
+    arrangement.section_starts(time_signature).into_iter().fold(
+        ArrangedTracks::default(),
+        |mut tracks, (start, section)| {
+            let section_tracks = arrange_section(section, start, &material, accompaniment, rng);
+
+            tracks.lead.extend(section_tracks.lead);
+            tracks.harmony.extend(section_tracks.harmony);
+            tracks.arpeggio.extend(section_tracks.arpeggio);
+            tracks.bass.extend(section_tracks.bass);
+            tracks.drums.extend(section_tracks.drums);
+            tracks
+        },
+    )
+}
+
+/// Arranges a single section that starts at the given time
+
+#[inline]
+fn arrange_section(
+    section: Section,
+    start: DeltaTime,
+    material: &LeadMaterial,
+    accompaniment: Accompaniment,
+    rng: &mut impl Rng,
+) -> ArrangedTracks {
+    let time_signature = material.time_signature;
+    let scale_notes = material.scale_notes;
+    let bar_time = time_signature.bar_time();
+    let instruments = section.instruments;
+
+    // Bridge starts from the middle of the progression,
+    // so extra chords are generated and skipped
+
+    let shift = match section.kind {
+        SectionKind::Bridge => BRIDGE_PROGRESSION_SHIFT,
+        _ => 0,
+    };
+
+    let harmony = generate_harmony(
+        accompaniment.progression,
+        scale_notes,
+        DEFAULT_CHORD_RANGE,
+        section.bars + shift,
+        time_signature,
+    )
+    .into_iter()
+    .skip(shift as usize)
+    .map(|chord| shifted(chord, start, shift * bar_time))
+    .collect::<Vec<_>>();
+
+    let mut tracks = ArrangedTracks::default();
+
+    if instruments.lead {
+        let bars = section_lead_bars(section.kind, material, rng);
+
+        tracks.lead = bars
+            .into_iter()
+            .cycle()
+            .take(section.bars as usize)
+            .enumerate()
+            .flat_map(|(bar, notes)| shifted(notes, start + bar as u32 * bar_time, 0))
+            .flat_map(|note| match section.kind {
+                SectionKind::Chorus => [Some(note), note.octave_up()],
+                _ => [Some(note), None],
+            })
+            .flatten()
+            .collect();
+    }
+
+    if instruments.arpeggio {
+        tracks.arpeggio = generate_arpeggio(
+            &harmony,
+            scale_notes,
+            accompaniment.arpeggio_rate,
+            accompaniment.arpeggio_pattern,
+            accompaniment.arpeggio_octaves,
+            rng,
+        );
+    }
+
+    if instruments.bass {
+        tracks.bass = generate_bass(
+            &harmony,
+            material.lead,
+            accompaniment.bass_pattern,
+            time_signature,
+        );
+    }
+
+    if instruments.drums {
+        tracks.drums = shifted(
+            generate_drums(
+                accompaniment.drum_pattern,
+                section.bars,
+                time_signature,
+                rng,
+            ),
+            start,
+            0,
+        );
+    }
+
+    if instruments.harmony {
+        tracks.harmony = harmony.into_iter().flatten().collect();
+    }
+
+    tracks
+}
+
+/// Bars of the lead material played in the section.
+/// Bars of the lead are matched with the parts of the melody type.
+/// Bars are repeated until the whole section is filled
+
+#[inline]
+fn section_lead_bars(
+    kind: SectionKind,
+    material: &LeadMaterial,
+    rng: &mut impl Rng,
+) -> Vec<Vec<NoteData>> {
+    let lead_bars = split_on_bars(material.lead, material.time_signature);
+    let form = material.melody_type.form();
+
+    match kind {
+        SectionKind::Intro | SectionKind::Outro => lead_bars.iter().take(1).cloned().collect(),
+        SectionKind::Verse | SectionKind::Chorus => lead_bars,
+
+        SectionKind::Bridge => {
+            let parts = form
+                .iter()
+                .zip(lead_bars.iter())
+                .filter(|(part, _)| **part != form[0])
+                .fold(
+                    Vec::<(char, Vec<NoteData>)>::new(),
+                    |mut parts, (&part, bar)| {
+                        if parts.iter().all(|(p, _)| *p != part) {
+                            parts.push((part, bar.clone()));
+                        }
+
+                        parts
+                    },
+                )
+                .into_iter()
+                .map(|(_, bar)| bar)
+                .collect::<Vec<_>>();
+
+            let parts = match parts.is_empty() {
+                true => lead_bars.iter().take(1).cloned().collect(),
+                false => parts,
+            };
+
+            parts
+                .into_iter()
+                .map(|bar| mutate(bar, material.scale_notes, BRIDGE_MUTATION_RATE, rng))
+                .collect()
+        }
+    }
+}
+
+/// Splits the lead on bars. Every note's absolute position
+/// is calculated from delays and lengths of the previous notes
+
+#[inline]
+fn split_on_bars(lead: &Vec<NoteData>, time_signature: TimeSignature) -> Vec<Vec<NoteData>> {
+    let bar_time = time_signature.bar_time();
+
+    lead.iter()
+        .scan(0, |prev_end, note| {
+            let start = *prev_end + note.delay();
+            *prev_end = start + note.length();
+            Some(((start / bar_time) as usize, *note))
+        })
+        .fold(Vec::new(), |mut bars: Vec<Vec<NoteData>>, (bar, note)| {
+            bars.resize_with(bars.len().max(bar + 1), Vec::new);
+            bars[bar].push(note);
+            bars
+        })
+}
+
+/// Moves notes' start times by the given offset:
+/// `offset` is added and then `back` is subtracted
+
+#[inline]
+fn shifted(notes: Vec<NoteData>, offset: DeltaTime, back: DeltaTime) -> Vec<NoteData> {
+    notes
+        .into_iter()
+        .map(|note| note.clone_with_new_start(note.start() + offset - back))
+        .collect()
+}
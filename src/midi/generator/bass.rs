@@ -0,0 +1,203 @@
+use crate::{
+    midi::{
+        generator::{get_bar_ratio, with_delays_from_starts},
+        time_signature::TimeSignature,
+    },
+    notes::{note::Note, note_data::*, ChordData},
+};
+
+use rand::Rng;
+use std::ops::RangeInclusive;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+/// Range of the bass notes (1-st and 2-nd octaves)
+pub const BASS_RANGE: RangeInclusive<Note> = Note::C1..=Note::B2;
+
+/// Minimal distance in semitones between
+/// the highest bass note and the lowest lead note
+const LEAD_CLEARANCE: i8 = 12;
+
+/// Velocity of the bass notes that start on the beat
+const ACCENT_VELOCITY: Velocity = 100;
+
+/// Velocity of all other bass notes
+const BASS_VELOCITY: Velocity = 85;
+
+/// Synthwave bassline patterns.
+/// Every pattern is described as the list of notes
+/// (semitones above the root, position and length in 1/16 steps)
+/// for a single bar of the given time signature
+
+#[derive(EnumIter, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BassPattern {
+    /// Root on every eighth
+    PulsingEighths,
+
+    /// Root on every eighth, notes between the beats are an octave higher
+    OctaveJumps,
+
+    /// Root on the beats, fifth on the other eighths
+    RootFifth,
+
+    /// Root of the chord, held for a half of the bar
+    ChordRoots,
+}
+
+impl BassPattern {
+    /// Generates random bass pattern
+
+    #[inline]
+    pub fn random_pattern(rng: &mut impl Rng) -> Self {
+        match rng.gen_range(0..4) {
+            0 => BassPattern::PulsingEighths,
+            1 => BassPattern::OctaveJumps,
+            2 => BassPattern::RootFifth,
+            _ => BassPattern::ChordRoots,
+        }
+    }
+
+    /// Notes of a single bar of the pattern
+
+    #[inline]
+    fn bar_notes(&self, time_signature: TimeSignature) -> Vec<(u8, u32, u32)> {
+        let steps = time_signature.steps_per_bar();
+        let beats = time_signature.beat_positions();
+        let eighths = (0..steps).step_by(2);
+
+        match self {
+            BassPattern::PulsingEighths => eighths.map(|pos| (0, pos, 2)).collect(),
+
+            BassPattern::OctaveJumps => eighths
+                .map(|pos| (if beats.contains(&pos) { 0 } else { 12 }, pos, 2))
+                .collect(),
+
+            BassPattern::RootFifth => eighths
+                .map(|pos| (if beats.contains(&pos) { 0 } else { 7 }, pos, 2))
+                .collect(),
+
+            BassPattern::ChordRoots => {
+                let half = steps / 4 * 2;
+                vec![(0, 0, half), (0, half, steps - half)]
+            }
+        }
+    }
+
+    /// Largest interval between the root and other notes of the pattern
+
+    #[inline]
+    fn max_interval(&self) -> u8 {
+        self.bar_notes(TimeSignature::COMMON)
+            .into_iter()
+            .map(|(interval, _, _)| interval)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Generates bassline that follows roots of the given chords.
+/// Every chord is played with the bass pattern for the whole chord's length.
+/// All notes lie in [BASS_RANGE] and stay at least an octave below the lead.
+/// Among all suitable octaves, root closest to the previous one is chosen.
+/// Produced notes have absolute start time
+/// and the delay from the end of the previous note
+
+#[inline]
+pub fn generate_bass(
+    harmony: &Vec<ChordData>,
+    lead: &Vec<NoteData>,
+    pattern: BassPattern,
+    time_signature: TimeSignature,
+) -> Vec<NoteData> {
+    let lead_floor = lead.iter().map(NoteData::note).min();
+    let bar_time = time_signature.bar_time();
+    let beats = time_signature.beat_positions();
+
+    // Notes that start on the beats are accented
+
+    let bar_notes = pattern
+        .bar_notes(time_signature)
+        .into_iter()
+        .map(|(interval, pos, len)| match beats.contains(&pos) {
+            true => (interval, pos, len, ACCENT_VELOCITY),
+            false => (interval, pos, len, BASS_VELOCITY),
+        })
+        .collect::<Vec<_>>();
+
+    let bar_notes = &bar_notes;
+
+    let bass = harmony
+        .iter()
+        .filter_map(|chord| {
+            let start = chord.iter().map(NoteData::start).min()?;
+            let length = chord.iter().map(NoteData::length).max()?;
+            Some((chord.first()?.note(), start, length))
+        })
+        .scan(
+            None,
+            |prev_root: &mut Option<Note>, (chord_root, start, length)| {
+                let root = bass_root(chord_root, pattern, lead_floor, *prev_root);
+                *prev_root = Some(root);
+                Some((root, start, length))
+            },
+        )
+        .flat_map(|(root, start, length)| {
+            (0..length / bar_time).flat_map(move |bar| {
+                bar_notes
+                    .iter()
+                    .filter_map(move |&(interval, pos, len, velocity)| {
+                        root.up(interval).map(|note| {
+                            NoteData::new(
+                                note,
+                                velocity,
+                                start + bar * bar_time + get_bar_ratio(pos),
+                                get_bar_ratio(len),
+                                0,
+                            )
+                        })
+                    })
+            })
+        })
+        .collect::<Vec<_>>();
+
+    with_delays_from_starts(bass)
+}
+
+/// Places chord's root in the bass range.
+/// Highest note of the pattern has to lie in [BASS_RANGE]
+/// and to be at least [LEAD_CLEARANCE] semitones lower than the lead.
+/// If there are no such places, the lowest one is taken
+
+#[inline]
+fn bass_root(
+    chord_root: Note,
+    pattern: BassPattern,
+    lead_floor: Option<Note>,
+    prev_root: Option<Note>,
+) -> Note {
+    let candidates = Note::iter()
+        .filter(|note| BASS_RANGE.contains(note))
+        .filter(|note| note.midi() % 12 == chord_root.midi() % 12)
+        .collect::<Vec<_>>();
+
+    let is_suitable = |root: &Note| {
+        root.up(pattern.max_interval())
+            .filter(|highest| BASS_RANGE.contains(highest))
+            .filter(|&highest| match lead_floor {
+                Some(lead) => lead - highest >= LEAD_CLEARANCE,
+                None => true,
+            })
+            .is_some()
+    };
+
+    candidates
+        .iter()
+        .copied()
+        .filter(is_suitable)
+        .min_by_key(|&root| match prev_root {
+            Some(prev) => ((root - prev).unsigned_abs(), u8::MAX - root.midi()),
+            None => (0, u8::MAX - root.midi()),
+        })
+        .or_else(|| candidates.first().copied())
+        .unwrap_or(chord_root)
+}
@@ -0,0 +1,258 @@
+use crate::{
+    midi::{generator::with_starts_from_delays, time_signature::TimeSignature},
+    notes::{note::Note, note_data::NoteData},
+};
+
+use itertools::Itertools;
+use rust_music_theory::note::PitchClass;
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// Scale degrees (starting from 1 on the tonic)
+/// that the phrases of the 8 bar loop end on.
+/// Last note of the 4-th bar targets the half cadence's degree,
+/// last note of the 8-th bar - the full cadence's degree
+///
+/// # Example
+/// ```
+/// use music_generator::midi::generator::cadence::CadencePlan;
+///
+/// assert_eq!(CadencePlan::default().targets(), vec![(4, 5), (8, 1)]);
+/// assert_eq!(CadencePlan::AUTHENTIC.targets(), vec![(8, 1)]);
+/// assert!(CadencePlan::NONE.targets().is_empty())
+/// ```
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct CadencePlan {
+    /// Degree of the 4-th bar's last note (5 for the half cadence)
+    pub half: Option<u8>,
+
+    /// Degree of the 8-th bar's last note (1 for the authentic cadence)
+    pub full: Option<u8>,
+}
+
+impl CadencePlan {
+    /// Bar that ends with the half cadence
+    pub const HALF_CADENCE_BAR: usize = 4;
+
+    /// Bar that ends with the full cadence
+    pub const FULL_CADENCE_BAR: usize = 8;
+
+    /// Half cadence on the 5-th degree and the authentic resolution to the tonic
+    pub const HALF_AND_AUTHENTIC: Self = Self {
+        half: Some(5),
+        full: Some(1),
+    };
+
+    /// Resolution to the tonic at the end of the loop only
+    pub const AUTHENTIC: Self = Self {
+        half: None,
+        full: Some(1),
+    };
+
+    /// Phrases end wherever the generation stops
+    pub const NONE: Self = Self {
+        half: None,
+        full: None,
+    };
+
+    /// Bars (counting from 1) with the degrees their last notes target
+
+    #[inline]
+    pub fn targets(&self) -> Vec<(usize, u8)> {
+        [
+            (Self::HALF_CADENCE_BAR, self.half),
+            (Self::FULL_CADENCE_BAR, self.full),
+        ]
+        .into_iter()
+        .filter_map(|(bar, degree)| degree.map(|degree| (bar, degree)))
+        .collect()
+    }
+}
+
+impl Default for CadencePlan {
+    #[inline]
+    fn default() -> Self {
+        Self::HALF_AND_AUTHENTIC
+    }
+}
+
+impl FromStr for CadencePlan {
+    type Err = ();
+
+    /// Parses degrees of the half and the full cadences
+    /// separated with the comma, `_` stands for no cadence
+    /// (e.g. `5,1`, `_,1`, `2,3`)
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let degree = |degree: &str| match degree.trim() {
+            "_" => Ok(None),
+            degree => match degree.parse() {
+                Ok(degree @ 1..=7) => Ok(Some(degree)),
+                _ => Err(()),
+            },
+        };
+
+        let (half, full) = s.split_once(',').ok_or(())?;
+
+        Ok(Self {
+            half: degree(half)?,
+            full: degree(full)?,
+        })
+    }
+}
+
+impl Display for CadencePlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let degree = |degree: Option<u8>| {
+            degree
+                .map(|degree| degree.to_string())
+                .unwrap_or_else(|| "_".to_string())
+        };
+
+        write!(f, "{},{}", degree(self.half), degree(self.full))
+    }
+}
+
+/// Pitch class (0 for C, 11 for B) of the scale's degree.
+/// Degrees above the number of the scale's notes continue in the next octave.
+/// Produces nothing if the key is not on the scale or degree is 0
+
+#[inline]
+pub fn degree_pitch_class(key: PitchClass, scale_notes: &[Note], degree: u8) -> Option<u8> {
+    let pitch_classes = scale_notes
+        .iter()
+        .map(|note| note.midi() % 12)
+        .unique()
+        .collect::<Vec<_>>();
+
+    let tonic = pitch_classes.iter().position(|&pc| pc == key.into_u8())?;
+    let degree = (degree as usize).checked_sub(1)?;
+
+    pitch_classes
+        .get((tonic + degree) % pitch_classes.len())
+        .copied()
+}
+
+/// Changes the note's pitch to the closest note
+/// of the scale with the given pitch class.
+/// Note is unchanged if there is no such note in the scale
+
+#[inline]
+pub fn resolve_to_pitch_class(note: NoteData, pitch_class: u8, scale_notes: &[Note]) -> NoteData {
+    scale_notes
+        .iter()
+        .filter(|scale_note| scale_note.midi() % 12 == pitch_class)
+        .min_by_key(|scale_note| scale_note.midi().abs_diff(note.note().midi()))
+        .map(|&resolved| note.clone_with_new_note(resolved))
+        .unwrap_or(note)
+}
+
+/// Indexes of the last notes of the bars by the bar's number (counting from 1)
+
+#[inline]
+pub fn bar_endings(lead: &[NoteData], time_signature: TimeSignature) -> Vec<(usize, usize)> {
+    let bar_time = time_signature.bar_time();
+
+    with_starts_from_delays(lead.to_vec())
+        .into_iter()
+        .enumerate()
+        .group_by(|(_, note)| note.start() / bar_time)
+        .into_iter()
+        .filter_map(|(bar, notes)| notes.last().map(|(i, _)| (bar as usize + 1, i)))
+        .collect()
+}
+
+/// Resolves the last notes of the phrases to the degrees of the plan.
+/// Octave is chosen to make the leaps from the previous note
+/// and to the next one as small as possible.
+/// Bars without notes are left silent
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     midi::{
+///         generator::cadence::{with_cadences, CadencePlan},
+///         time_signature::TimeSignature,
+///     },
+///     notes::{note::Note, note_data::NoteData},
+/// };
+/// use rust_music_theory::note::PitchClass;
+///
+/// let scale = [60, 62, 64, 65, 67, 69, 71].map(|midi: u8| Note::try_from(midi).unwrap());
+/// let bar = NoteData::new(scale[2], 75, 0, 512, 0);
+///
+/// let lead = with_cadences(
+///     vec![bar; 8],
+///     CadencePlan::default(),
+///     PitchClass::C,
+///     &scale,
+///     TimeSignature::COMMON,
+/// );
+///
+/// assert_eq!(lead[3].note(), scale[4]);
+/// assert_eq!(lead[7].note(), scale[0]);
+/// assert_eq!(lead[6].note(), scale[2])
+/// ```
+
+#[inline]
+pub fn with_cadences(
+    mut lead: Vec<NoteData>,
+    plan: CadencePlan,
+    key: PitchClass,
+    scale_notes: &[Note],
+    time_signature: TimeSignature,
+) -> Vec<NoteData> {
+    let endings = bar_endings(&lead, time_signature);
+
+    for (bar, degree) in plan.targets() {
+        let ending = endings.iter().find(|(ending_bar, _)| *ending_bar == bar);
+
+        if let (Some(&(_, i)), Some(pitch_class)) =
+            (ending, degree_pitch_class(key, scale_notes, degree))
+        {
+            lead[i] = resolve_between_neighbours(&lead, i, pitch_class, scale_notes);
+        }
+    }
+
+    lead
+}
+
+/// Changes the pitch of the lead's note to the note of the scale
+/// with the given pitch class and the smallest leap to the neighbours.
+/// Leaps being equal, the closest to the original pitch is chosen
+
+#[inline]
+fn resolve_between_neighbours(
+    lead: &[NoteData],
+    i: usize,
+    pitch_class: u8,
+    scale_notes: &[Note],
+) -> NoteData {
+    let note = lead[i];
+
+    let neighbours = [i.checked_sub(1), Some(i + 1)]
+        .into_iter()
+        .flatten()
+        .filter_map(|j| lead.get(j))
+        .map(|neighbour| neighbour.note().midi())
+        .collect::<Vec<_>>();
+
+    scale_notes
+        .iter()
+        .filter(|scale_note| scale_note.midi() % 12 == pitch_class)
+        .min_by_key(|scale_note| {
+            let leap = neighbours
+                .iter()
+                .map(|&neighbour| scale_note.midi().abs_diff(neighbour))
+                .max()
+                .unwrap_or(0);
+
+            (leap, scale_note.midi().abs_diff(note.note().midi()))
+        })
+        .map(|&resolved| note.clone_with_new_note(resolved))
+        .unwrap_or(note)
+}
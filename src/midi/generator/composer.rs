@@ -1,5 +1,8 @@
-use crate::notes::{note_data::*, ChordData};
-use ghakuf::messages::{Message, MidiEvent};
+use crate::{
+    midi::{bpm::BPM, resolution::Resolution, time_signature::TimeSignature},
+    notes::{note_data::*, ChordData},
+};
+use ghakuf::messages::{Message, MetaEvent, MidiEvent};
 
 /// Constructs vector of ON and OFF MIDI events.
 /// ON event is happened after the note's delay,
@@ -65,6 +68,203 @@ where
         .collect()
 }
 
+/// Constructs vector of MIDI messages from the generated harmony.
+/// Chords are composed one after another with [compose_chord]
+
+#[inline]
+pub fn compose_harmony_from_generated(generated_harmony: Vec<ChordData>) -> Vec<Message> {
+    generated_harmony
+        .into_iter()
+        .flat_map(compose_chord)
+        .collect()
+}
+
+/// Constructs vector of ON/OFF MIDI events
+/// from the sequence of notes (see [compose_note])
+/// and moves all events to the given channel
+
+#[inline]
+pub fn compose_notes_on_channel(notes: Vec<NoteData>, channel: u8) -> Vec<Message> {
+    compose_lead_from_generated(notes, compose_note)
+        .iter()
+        .map(|msg| change_note_msg_channel(msg, channel))
+        .collect()
+}
+
+/// Constructs vector of ON/OFF MIDI events from notes
+/// that may sound simultaneously (drums, overlapping notes, etc.).
+/// Unlike [compose_note], notes' start time is used
+/// as the absolute position of the note in the track.
+/// At the same moment, OFF events are placed before ON events
+
+#[inline]
+pub fn compose_polyphonic(notes: Vec<NoteData>, channel: u8) -> Vec<Message> {
+    let mut events = notes
+        .into_iter()
+        .flat_map(|nd| {
+            [
+                (nd.start(), true, nd),
+                (nd.start() + nd.length(), false, nd),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    events.sort_by_key(|(time, is_on, _)| (*time, *is_on));
+
+    events
+        .into_iter()
+        .scan(0, |prev_time, (time, is_on, nd)| {
+            let delta_time = time - *prev_time;
+            *prev_time = time;
+
+            Some(match is_on {
+                true => nd.into_on_midi_event(delta_time, channel),
+                false => nd.into_off_midi_event(delta_time, channel),
+            })
+        })
+        .collect()
+}
+
+/// Constructs vector of [MetaEvent::Marker] events
+/// from the markers' names and their absolute time
+
+#[inline]
+pub fn compose_markers(markers: Vec<(DeltaTime, String)>) -> Vec<Message> {
+    markers
+        .into_iter()
+        .scan(0, |prev_time, (time, name)| {
+            let delta_time = time - *prev_time;
+            *prev_time = time;
+
+            Some(Message::MetaEvent {
+                delta_time,
+                event: MetaEvent::Marker,
+                data: name.into_bytes(),
+            })
+        })
+        .collect()
+}
+
+/// Constructs [MetaEvent::TimeSignature] event
+/// that is placed at the start of the track
+
+#[inline]
+pub fn compose_time_signature(time_signature: TimeSignature) -> Message {
+    Message::MetaEvent {
+        delta_time: 0,
+        event: MetaEvent::TimeSignature,
+        data: time_signature.meta_event_data(),
+    }
+}
+
+/// Constructs [MetaEvent::SetTempo] event
+/// that is placed at the start of the track
+
+#[inline]
+pub fn compose_tempo(bpm: impl BPM) -> Message {
+    let tempo = bpm.tempo();
+
+    Message::MetaEvent {
+        delta_time: 0,
+        event: MetaEvent::SetTempo,
+        data: vec![(tempo >> 16) as u8, (tempo >> 8) as u8, tempo as u8],
+    }
+}
+
+/// Constructs [MetaEvent::EndOfTrack] event
+
+#[inline]
+pub fn compose_end_of_track() -> Message {
+    Message::MetaEvent {
+        delta_time: 0,
+        event: MetaEvent::EndOfTrack,
+        data: Vec::new(),
+    }
+}
+
+/// Constructs [MidiEvent::ProgramChange] event
+/// that sets the instrument of the channel
+
+#[inline]
+pub fn compose_program_change(channel: u8, program: u8) -> Message {
+    Message::MidiEvent {
+        delta_time: 0,
+        event: MidiEvent::ProgramChange {
+            ch: channel,
+            program,
+        },
+    }
+}
+
+/// Constructs [MidiEvent::ControlChange] event
+/// that sets the controller's value of the channel
+
+#[inline]
+pub fn compose_control_change(channel: u8, control: u8, data: u8) -> Message {
+    Message::MidiEvent {
+        delta_time: 0,
+        event: MidiEvent::ControlChange {
+            ch: channel,
+            control,
+            data,
+        },
+    }
+}
+
+/// Converts delta time of all messages
+/// from one resolution to another. Absolute time
+/// of every message in the track is rescaled,
+/// so rounding errors are not accumulated along the track
+
+#[inline]
+pub fn rescale_messages(messages: Vec<Message>, from: Resolution, to: Resolution) -> Vec<Message> {
+    messages
+        .into_iter()
+        .scan((0, 0), |(time, rescaled_time), message| {
+            if let Message::TrackChange = message {
+                (*time, *rescaled_time) = (0, 0);
+                return Some(message);
+            }
+
+            let mut rescale = |delta_time: u32| {
+                *time += delta_time;
+                let prev_time = *rescaled_time;
+                *rescaled_time = from.rescale(*time, to);
+                *rescaled_time - prev_time
+            };
+
+            Some(match message {
+                Message::MidiEvent { delta_time, event } => Message::MidiEvent {
+                    delta_time: rescale(delta_time),
+                    event,
+                },
+
+                Message::MetaEvent {
+                    delta_time,
+                    event,
+                    data,
+                } => Message::MetaEvent {
+                    delta_time: rescale(delta_time),
+                    event,
+                    data,
+                },
+
+                Message::SysExEvent {
+                    delta_time,
+                    event,
+                    data,
+                } => Message::SysExEvent {
+                    delta_time: rescale(delta_time),
+                    event,
+                    data,
+                },
+
+                Message::TrackChange => unreachable!(),
+            })
+        })
+        .collect()
+}
+
 #[inline]
 pub fn change_note_msg_channel(midi_msg: &Message, channel: u8) -> Message {
     match midi_msg {
@@ -0,0 +1,240 @@
+use crate::{
+    midi::{generator::get_bar_ratio, time_signature::TimeSignature},
+    notes::{note::Note, note_data::*},
+};
+
+use rand::Rng;
+use strum_macros::EnumIter;
+
+/// MIDI channel reserved for the percussion by General MIDI (channel 10)
+pub const DRUMS_CHANNEL: u8 = 9;
+
+/// Number of bars in a single phrase.
+/// Fills are played in the last bar of every phrase
+const PHRASE_BARS: u32 = 4;
+
+/// Percussion instruments mapped to General MIDI percussion notes
+
+#[derive(EnumIter, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DrumInstrument {
+    Kick,
+    Snare,
+    Clap,
+    ClosedHat,
+    OpenHat,
+    LowTom,
+    MidTom,
+    HighTom,
+    Crash,
+}
+
+impl DrumInstrument {
+    /// Gets General MIDI percussion note of the instrument
+    ///
+    /// # Example
+    /// ```
+    /// use music_generator::midi::generator::drums::DrumInstrument;
+    /// assert_eq!(DrumInstrument::Kick.note().midi(), 36);
+    /// assert_eq!(DrumInstrument::Snare.note().midi(), 38)
+    /// ```
+
+    #[inline]
+    pub fn note(&self) -> Note {
+        match self {
+            DrumInstrument::Kick => Note::C2,
+            DrumInstrument::Snare => Note::D2,
+            DrumInstrument::Clap => Note::Eb2,
+            DrumInstrument::ClosedHat => Note::Gb2,
+            DrumInstrument::OpenHat => Note::Bb2,
+            DrumInstrument::LowTom => Note::A2,
+            DrumInstrument::MidTom => Note::C3,
+            DrumInstrument::HighTom => Note::D3,
+            DrumInstrument::Crash => Note::Db3,
+        }
+    }
+}
+
+/// Synthwave drum grooves.
+/// Every groove is described as the list of hits
+/// (instrument, position in 1/16 steps, velocity)
+/// for a single bar of the given time signature
+
+#[derive(EnumIter, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DrumPattern {
+    /// Kick on every beat, snare with clap on every second beat
+    /// (2 and 4 in 4/4), closed hats on beats and open hats between them
+    FourOnTheFloor,
+
+    /// Kick on 1 with syncopated pickup, snare with clap
+    /// in the middle of the bar (3 in 4/4), closed hats on eighths
+    HalfTime,
+}
+
+impl DrumPattern {
+    /// Generates random drum pattern
+
+    #[inline]
+    pub fn random_pattern(rng: &mut impl Rng) -> Self {
+        match rng.gen_range(0..2) {
+            0 => DrumPattern::FourOnTheFloor,
+            _ => DrumPattern::HalfTime,
+        }
+    }
+
+    /// Hits of a single bar of the groove
+
+    #[inline]
+    fn bar_hits(&self, time_signature: TimeSignature) -> Vec<(DrumInstrument, u32, Velocity)> {
+        let steps = time_signature.steps_per_bar();
+        let beats = time_signature.beat_positions();
+
+        match self {
+            DrumPattern::FourOnTheFloor => beats
+                .iter()
+                .map(|&pos| (DrumInstrument::Kick, pos, 110))
+                .chain(beats.iter().skip(1).step_by(2).flat_map(|&pos| {
+                    [
+                        (DrumInstrument::Snare, pos, 105),
+                        (DrumInstrument::Clap, pos, 90),
+                    ]
+                }))
+                .chain(
+                    beats
+                        .iter()
+                        .map(|&pos| (DrumInstrument::ClosedHat, pos, 70)),
+                )
+                .chain(beats.iter().zip(beats.iter().skip(1).chain([&steps])).map(
+                    |(&beat, &next_beat)| (DrumInstrument::OpenHat, (beat + next_beat) / 2, 75),
+                ))
+                .collect(),
+
+            DrumPattern::HalfTime => {
+                let snare = beats[beats.len() / 2];
+
+                [0, snare + 2]
+                    .into_iter()
+                    .filter(|&pos| pos < steps)
+                    .map(|pos| (DrumInstrument::Kick, pos, 110))
+                    .chain([
+                        (DrumInstrument::Snare, snare, 110),
+                        (DrumInstrument::Clap, snare, 95),
+                    ])
+                    .chain(
+                        (0..steps)
+                            .step_by(2)
+                            .map(|pos| (DrumInstrument::ClosedHat, pos, 70)),
+                    )
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Generates drums for the given number of bars
+/// with the 1/16 grid, the same as the lead melody uses.
+/// Last beat of every 4-th bar (end of the phrase)
+/// is replaced with the gated snare fill (short snare hits
+/// on every 1/16 with the rising velocity). Fill at the end
+/// of the 8 bar loop is also played with toms.
+/// Phrases after fills are started with the crash.
+/// Velocities are slightly humanized.
+/// Produced hits have absolute start time
+/// and have to be composed with the polyphonic composer
+
+#[inline]
+pub fn generate_drums(
+    pattern: DrumPattern,
+    bars: u32,
+    time_signature: TimeSignature,
+    rng: &mut impl Rng,
+) -> Vec<NoteData> {
+    let bar_time = time_signature.bar_time();
+    let steps = time_signature.steps_per_bar();
+    let last_beat = time_signature
+        .beat_positions()
+        .last()
+        .copied()
+        .unwrap_or_default();
+
+    let mut drums = (0..bars)
+        .flat_map(|bar| {
+            let is_phrase_end = (bar + 1) % PHRASE_BARS == 0;
+            let is_loop_end = (bar + 1) % (PHRASE_BARS * 2) == 0;
+
+            let groove = pattern
+                .bar_hits(time_signature)
+                .into_iter()
+                .filter(move |(_, pos, _)| !is_phrase_end || *pos < last_beat);
+
+            let fill = match (is_phrase_end, is_loop_end) {
+                (false, _) => Vec::new(),
+                (true, false) => snare_fill(last_beat, steps),
+                (true, true) => tom_fill(last_beat, steps),
+            };
+
+            let crash = match bar % PHRASE_BARS == 0 && bar != 0 {
+                true => vec![(DrumInstrument::Crash, 0, 100)],
+                false => Vec::new(),
+            };
+
+            groove
+                .chain(fill)
+                .chain(crash)
+                .map(move |(instrument, pos, velocity)| (instrument, bar, pos, velocity))
+        })
+        .map(|(instrument, bar, pos, velocity)| {
+            let velocity = (velocity as i32 + rng.gen_range(-6..=6)).clamp(1, 127) as Velocity;
+
+            NoteData::new(
+                instrument.note(),
+                velocity,
+                bar * bar_time + get_bar_ratio(pos),
+                get_bar_ratio(1),
+                0,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    drums.sort();
+    drums
+}
+
+/// Gated snare roll from the given position to the end of the bar.
+/// Velocity rises from 85 to 120
+
+#[inline]
+fn snare_fill(from: u32, steps: u32) -> Vec<(DrumInstrument, u32, Velocity)> {
+    let len = (steps - from).max(2);
+
+    std::iter::once((DrumInstrument::Kick, from, 110))
+        .chain((from..steps).map(|pos| {
+            let velocity = 85 + (pos - from) * 35 / (len - 1);
+            (DrumInstrument::Snare, pos, velocity as Velocity)
+        }))
+        .collect()
+}
+
+/// Tom roll down from the given position
+/// with the gated snare on the last 1/16 of the bar
+
+#[inline]
+fn tom_fill(from: u32, steps: u32) -> Vec<(DrumInstrument, u32, Velocity)> {
+    let toms = [
+        (DrumInstrument::HighTom, 100),
+        (DrumInstrument::MidTom, 105),
+        (DrumInstrument::LowTom, 110),
+    ];
+
+    let len = (steps - from).max(2);
+
+    std::iter::once((DrumInstrument::Kick, from, 110))
+        .chain((from..steps - 1).map(|pos| {
+            let (tom, velocity) = toms[((pos - from) * 3 / (len - 1)) as usize];
+            (tom, pos, velocity)
+        }))
+        .chain([
+            (DrumInstrument::Snare, steps - 1, 120),
+            (DrumInstrument::Clap, steps - 1, 100),
+        ])
+        .collect()
+}
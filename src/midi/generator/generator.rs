@@ -1,7 +1,9 @@
 use crate::{
+    error::Result,
     midi::{
         bpm::BPM,
         generator::{get_bar_ratio, random_from_vec},
+        time_signature::{TimeSignature, STEP_TIME},
     },
     notes::{note::Note, note_data::*},
 };
@@ -14,8 +16,7 @@ const DIRECTION_UP: u32 = 0;
 const DIRECTION_DOWN: u32 = 1;
 
 #[inline]
-pub fn generate_bpm() -> impl BPM {
-    let mut rng = rand::thread_rng();
+pub fn generate_bpm(rng: &mut impl Rng) -> impl BPM {
     rng.gen_range(90..=120)
 }
 
@@ -23,8 +24,8 @@ pub fn generate_bpm() -> impl BPM {
 /// Number is in the set of 4..=8
 
 #[inline]
-pub fn generate_synthwave_melody_length() -> usize {
-    rand::thread_rng().gen_range(4..=8)
+pub fn generate_synthwave_melody_length(rng: &mut impl Rng) -> usize {
+    rng.gen_range(4..=8)
 }
 
 /// Tries to get a note in the scale list by the given note
@@ -35,7 +36,7 @@ pub fn generate_synthwave_melody_length() -> usize {
 #[inline]
 fn map_index<F>(tonic_note: Note, scale_notes: &Vec<Note>, transform: F) -> Option<Note>
 where
-    F: Fn(usize) -> usize,
+    F: FnOnce(usize) -> usize,
 {
     let pos = scale_notes.iter().position(|&nt| nt == tonic_note)?;
     scale_notes.get(transform(pos)).map(|&nt| nt)
@@ -47,15 +48,22 @@ where
 /// Direction is chosen as [DIRECTION_UP] or [DIRECTION_DOWN]
 
 #[inline]
-fn rand_close_note(tonic_note: Note, scale_notes: &Vec<Note>, up_down_direction: u32) -> Note {
+fn rand_close_note(
+    tonic_note: Note,
+    scale_notes: &Vec<Note>,
+    up_down_direction: u32,
+    rng: &mut impl Rng,
+) -> Note {
     match up_down_direction {
         DIRECTION_UP => map_index(tonic_note, scale_notes, |pos| {
             let mut notes_dif = (1..=3).collect::<Vec<_>>();
-            pos + random_from_vec(&mut notes_dif).unwrap()
+            pos + random_from_vec(&mut notes_dif, rng).unwrap_or(1)
         })
         .unwrap_or(tonic_note),
 
-        DIRECTION_DOWN => map_index(tonic_note, scale_notes, |pos| pos - 1).unwrap_or(tonic_note),
+        DIRECTION_DOWN => {
+            map_index(tonic_note, scale_notes, |pos| pos.wrapping_sub(1)).unwrap_or(tonic_note)
+        }
 
         _ => unreachable!(),
     }
@@ -74,9 +82,12 @@ fn rand_close_note_data(
     start_position: u32,
     len: DeltaTime,
     delay_ratio: u32,
+    rng: &mut impl Rng,
 ) -> NoteData {
+    let direction = rng.gen::<u32>() % 2;
+
     NoteData::new(
-        rand_close_note(tonic_note, scale_notes, rand::thread_rng().gen::<u32>() % 2),
+        rand_close_note(tonic_note, scale_notes, direction, rng),
         75,
         get_bar_ratio(start_position),
         len,
@@ -86,9 +97,10 @@ fn rand_close_note_data(
 
 /// Generates the lead melody from the given Key, scale and BPM.
 /// For the lead melody, next algorithm is used:
-/// Separates bar onto 16 parts, then for each
+/// Separates bar onto 1/16 steps (16 for 4/4), then for each
 /// position either puts note with length 1/16 of bar,
-/// or skips it. Only single pause with 2/16 length is allowed
+/// or skips it. Allowed lengths depend on the position in the beat.
+/// Only single pause with 2/16 length is allowed
 /// Pause with 3/16 and greater are not allowed.
 /// Chosen notes are close to the key and lie on scale
 
@@ -97,20 +109,22 @@ pub fn generate_lead_melody_with_bpm_and_len(
     key: PitchClass,
     scale_notes: &Vec<Note>,
     lead_len: usize,
-) -> Vec<NoteData> {
+    time_signature: TimeSignature,
+    rng: &mut impl Rng,
+) -> Result<Vec<NoteData>> {
     let mut full_lens = vec![1, 2, 4];
     let mut even_lens = vec![1, 2];
     let mut odd_lens = vec![1];
 
-    let tonic_len = random_from_vec(&mut even_lens).unwrap();
+    let tonic_len = random_from_vec(&mut even_lens, rng).unwrap_or(1);
     let tonic_time = get_bar_ratio(tonic_len);
-    let tonic_note = generate_tonic_lead_note(key, 75, tonic_time, 0);
+    let tonic_note = generate_tonic_lead_note(key, 75, tonic_time, 0)?;
 
     let mut lead = vec![tonic_note];
     let mut cur_pos = tonic_len;
 
-    while cur_pos < 16 && lead.len() < lead_len {
-        let prev_note = *lead.last().unwrap();
+    while cur_pos < time_signature.steps_per_bar() && lead.len() < lead_len {
+        let prev_note = lead.last().copied().unwrap_or(tonic_note);
         let current_position = cur_pos;
 
         let mut push_next_mb = |lens: &mut Vec<DeltaTime>| {
@@ -121,19 +135,18 @@ pub fn generate_lead_melody_with_bpm_and_len(
                 tonic_note,
                 prev_note,
                 &mut cur_pos,
+                rng,
             )
         };
 
-        match current_position % 4 {
+        match time_signature.position_in_beat(current_position) {
             0 => push_next_mb(&mut full_lens),
-            1 => push_next_mb(&mut odd_lens),
-            2 => push_next_mb(&mut even_lens),
-            3 => push_next_mb(&mut odd_lens),
-            _ => unreachable!(),
+            pos if pos % 2 == 1 => push_next_mb(&mut odd_lens),
+            _ => push_next_mb(&mut even_lens),
         }
     }
 
-    lead
+    Ok(lead)
 }
 
 #[inline]
@@ -144,10 +157,13 @@ fn push_next_note_or_skip(
     tonic_note: NoteData,
     prev_note: NoteData,
     cur_pos: &mut DeltaTime,
+    rng: &mut impl Rng,
 ) {
-    let len = random_from_vec(lens).unwrap();
+    let len = random_from_vec(lens, rng).unwrap_or(1);
     let note_time = get_bar_ratio(len);
-    let next_note_mb = next_note_or_skip(note_time, tonic_note, scale_notes, prev_note, *cur_pos);
+
+    let next_note_mb =
+        next_note_or_skip(note_time, tonic_note, scale_notes, prev_note, *cur_pos, rng);
 
     match next_note_mb {
         None => *cur_pos += 1,
@@ -166,20 +182,24 @@ fn next_note_or_skip(
     scale_notes: &Vec<Note>,
     prev_note: NoteData,
     position: DeltaTime,
+    rng: &mut impl Rng,
 ) -> Option<NoteData> {
-    let prev_note_start = prev_note.start() / 32;
-    let prev_note_len = prev_note.length() / 32;
+    let prev_note_start = prev_note.start() / STEP_TIME;
+    let prev_note_len = prev_note.length() / STEP_TIME;
 
     let cur_delay = position - prev_note_start - prev_note_len;
-    let next = || next_note(len, tonic_note, scale_notes, position, cur_delay);
-
-    match cur_delay {
-        3 => Some(next()),
 
-        _ => match rand::thread_rng().gen_bool(0.25) {
-            true => Some(next()),
-            false => None,
-        },
+    match cur_delay == 3 || rng.gen_bool(0.25) {
+        true => Some(next_note(
+            len,
+            tonic_note,
+            scale_notes,
+            position,
+            cur_delay,
+            rng,
+        )),
+
+        false => None,
     }
 }
 
@@ -190,8 +210,16 @@ fn next_note(
     scale_notes: &Vec<Note>,
     position: DeltaTime,
     cur_delay: DeltaTime,
+    rng: &mut impl Rng,
 ) -> NoteData {
-    rand_close_note_data(tonic_note.note(), scale_notes, position, len, cur_delay)
+    rand_close_note_data(
+        tonic_note.note(),
+        scale_notes,
+        position,
+        len,
+        cur_delay,
+        rng,
+    )
 }
 
 /// Generates tonic lead note with the given key.
@@ -203,8 +231,9 @@ fn generate_tonic_lead_note(
     velocity: Velocity,
     length: DeltaTime,
     delay: DeltaTime,
-) -> NoteData {
-    NoteData::new(Note::from(MTNote::new(key, 5)), velocity, 0, length, delay)
+) -> Result<NoteData> {
+    let note = Note::try_from(MTNote::new(key, 5))?;
+    Ok(NoteData::new(note, velocity, 0, length, delay))
 }
 
 /// Fixes note's pitch to lie on the scale.
@@ -237,7 +266,7 @@ fn randomize_note_with_given_diff(
         ),
 
         DIRECTION_DOWN => note.clone_with_new_note(
-            map_index(note.note(), scale_notes, |pos| pos - 1).unwrap_or(note.note()),
+            map_index(note.note(), scale_notes, |pos| pos.wrapping_sub(1)).unwrap_or(note.note()),
         ),
 
         _ => unreachable!(),
@@ -254,10 +283,11 @@ fn randomize_note_with_given_diff(
 /// All produced notes lie on the scale
 
 #[inline]
-pub fn randomize_note(note: NoteData, scale_notes: &Vec<Note>) -> NoteData {
+pub fn randomize_note(note: NoteData, scale_notes: &Vec<Note>, rng: &mut impl Rng) -> NoteData {
     let mut diffs = (0..=6).collect::<Vec<_>>();
-    let diff = random_from_vec(&mut diffs).unwrap();
-    let direction = random_from_vec(&mut vec![DIRECTION_UP, DIRECTION_DOWN]).unwrap();
+    let diff = random_from_vec(&mut diffs, rng).unwrap_or_default();
+    let direction =
+        random_from_vec(&mut vec![DIRECTION_UP, DIRECTION_DOWN], rng).unwrap_or(DIRECTION_UP);
     randomize_note_with_given_diff(note, scale_notes, direction, diff)
 }
 
@@ -272,9 +302,10 @@ pub fn randomize_lead(
     generated_lead: Vec<NoteData>,
     scale_notes: &Vec<Note>,
     direction: u32,
+    rng: &mut impl Rng,
 ) -> Vec<NoteData> {
     let mut diffs = (0..=2).collect::<Vec<_>>();
-    let diff = random_from_vec(&mut diffs).unwrap();
+    let diff = random_from_vec(&mut diffs, rng).unwrap_or_default();
 
     generated_lead
         .into_iter()
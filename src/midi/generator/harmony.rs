@@ -0,0 +1,208 @@
+use crate::{
+    midi::time_signature::TimeSignature,
+    notes::{note::Note, note_data::*, ChordData},
+};
+
+use itertools::Itertools;
+use rand::Rng;
+use std::ops::RangeInclusive;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+/// Range of notes used for the chords by default.
+/// Lies below the lead melody, that starts from the 5-th octave
+pub const DEFAULT_CHORD_RANGE: RangeInclusive<Note> = Note::C3..=Note::C5;
+
+/// Velocity of all chord notes
+const CHORD_VELOCITY: Velocity = 60;
+
+/// Diatonic chord progressions, common for synthwave.
+/// Chords are built on the degrees of the chosen scale,
+/// so the quality of every chord depends on the scale and mode
+/// (e.g. I–V–vi–IV in Aeolian mode turns into i–v–VI–iv)
+
+#[derive(EnumIter, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ChordProgression {
+    /// I – V – vi – IV
+    OneFiveSixFour,
+
+    /// i – VI – III – VII
+    OneSixThreeSeven,
+
+    /// vi – IV – I – V
+    SixFourOneFive,
+
+    /// i – iv – VII – III
+    OneFourSevenThree,
+
+    /// i – VII – VI – VII
+    OneSevenSixSeven,
+
+    /// I – vi – IV – V
+    OneSixFourFive,
+}
+
+impl ChordProgression {
+    /// Generates random progression
+
+    #[inline]
+    pub fn random_progression(rng: &mut impl Rng) -> Self {
+        match rng.gen_range(0..6) {
+            0 => ChordProgression::OneFiveSixFour,
+            1 => ChordProgression::OneSixThreeSeven,
+            2 => ChordProgression::SixFourOneFive,
+            3 => ChordProgression::OneFourSevenThree,
+            4 => ChordProgression::OneSevenSixSeven,
+            _ => ChordProgression::OneSixFourFive,
+        }
+    }
+
+    /// Scale degrees (starting from 0) of the chords' roots
+
+    #[inline]
+    pub fn degrees(&self) -> [usize; 4] {
+        match self {
+            ChordProgression::OneFiveSixFour => [0, 4, 5, 3],
+            ChordProgression::OneSixThreeSeven => [0, 5, 2, 6],
+            ChordProgression::SixFourOneFive => [5, 3, 0, 4],
+            ChordProgression::OneFourSevenThree => [0, 3, 6, 2],
+            ChordProgression::OneSevenSixSeven => [0, 6, 5, 6],
+            ChordProgression::OneSixFourFive => [0, 5, 3, 4],
+        }
+    }
+}
+
+/// Generates harmony for the given number of bars.
+/// Every bar is filled with a single triad from the progression.
+/// Progression is repeated until all bars are filled,
+/// so 8 bar lead receives the progression twice.
+/// If some chord can not be placed in the range, harmony is cut on it.
+/// Chords are voiced within the given range,
+/// each next chord is the closest one to the previous.
+/// First note of every chord is its root (not always the lowest one).
+/// Produced notes have absolute start time and zero delay
+
+#[inline]
+pub fn generate_harmony(
+    progression: ChordProgression,
+    scale_notes: &Vec<Note>,
+    range: RangeInclusive<Note>,
+    bars: u32,
+    time_signature: TimeSignature,
+) -> Vec<ChordData> {
+    let degrees = scale_degrees(scale_notes);
+    let bar_time = time_signature.bar_time();
+
+    if degrees.is_empty() {
+        return Vec::new();
+    }
+
+    // Progression is voiced once, so every repetition sounds the same
+
+    let voicings = progression
+        .degrees()
+        .into_iter()
+        .scan(None, |prev_voicing: &mut Option<Vec<Note>>, degree| {
+            let triad = triad_pitches(&degrees, degree);
+            let voicing = voice_chord(&triad, &range, prev_voicing.as_ref())?;
+            *prev_voicing = Some(voicing.clone());
+            Some(voicing)
+        })
+        .collect::<Vec<_>>();
+
+    voicings
+        .into_iter()
+        .cycle()
+        .take(bars as usize)
+        .enumerate()
+        .map(|(bar, voicing)| {
+            voicing
+                .into_iter()
+                .map(|note| NoteData::new(note, CHORD_VELOCITY, bar as u32 * bar_time, bar_time, 0))
+                .collect::<ChordData>()
+        })
+        .collect()
+}
+
+/// Picks the first seven unique degrees of the scale,
+/// starting from the tonic (the first note of the scale)
+
+#[inline]
+pub fn scale_degrees(scale_notes: &Vec<Note>) -> Vec<Note> {
+    let tonic = match scale_notes.first() {
+        Some(tonic) => *tonic,
+        None => return Vec::new(),
+    };
+
+    scale_notes
+        .iter()
+        .copied()
+        .filter(|note| note.midi() >= tonic.midi() && note.midi() < tonic.midi() + 12)
+        .sorted()
+        .dedup()
+        .collect()
+}
+
+/// Constructs triad on the given scale degree by stacking thirds.
+/// Result contains MIDI values of the root, the third and the fifth
+
+#[inline]
+fn triad_pitches(degrees: &Vec<Note>, degree: usize) -> Vec<u8> {
+    let len = degrees.len();
+
+    (0..3)
+        .map(|i| degree + i * 2)
+        .map(|pos| degrees[pos % len].midi() + (pos / len) as u8 * 12)
+        .collect()
+}
+
+/// Places every pitch of the chord in the range.
+/// Among all placements, the closest one is chosen:
+/// with the smallest span and the smallest
+/// movement from the previous voicing (if any).
+/// Notes keep the order of pitches, so the root is the first one
+
+#[inline]
+fn voice_chord(
+    pitches: &Vec<u8>,
+    range: &RangeInclusive<Note>,
+    prev_voicing: Option<&Vec<Note>>,
+) -> Option<Vec<Note>> {
+    pitches
+        .iter()
+        .map(|&pitch| {
+            Note::iter()
+                .filter(|note| range.contains(note))
+                .filter(|note| note.midi() % 12 == pitch % 12)
+                .collect::<Vec<_>>()
+        })
+        .multi_cartesian_product()
+        .filter(|voicing| voicing.iter().sorted().dedup().count() == pitches.len())
+        .min_by_key(|voicing| voicing_cost(voicing, prev_voicing))
+}
+
+/// Cost of the voicing: span between the lowest and the highest notes
+/// and the sum of distances between notes of the current and the previous voicings
+
+#[inline]
+fn voicing_cost(voicing: &Vec<Note>, prev_voicing: Option<&Vec<Note>>) -> u32 {
+    let span = voicing
+        .iter()
+        .minmax()
+        .into_option()
+        .map(|(&lowest, &highest)| (highest - lowest).unsigned_abs() as u32)
+        .unwrap_or(0);
+
+    let movement = prev_voicing
+        .map(|prev| {
+            voicing
+                .iter()
+                .sorted()
+                .zip(prev.iter().sorted())
+                .map(|(&cur, &prev)| (cur - prev).unsigned_abs() as u32)
+                .sum::<u32>()
+        })
+        .unwrap_or(0);
+
+    span * 2 + movement
+}
@@ -1,13 +1,23 @@
-use crate::notes::note_data::*;
+use crate::{
+    midi::time_signature::{TimeSignature, STEP_TIME},
+    notes::note_data::*,
+};
 use astro_float::{ctx::Context, Consts, RoundingMode};
 use rand::{prelude::SliceRandom, Rng};
 
 #[deprecated]
 pub mod analyzer;
 
+pub mod arpeggiator;
 pub mod arpeggio_types;
+pub mod arrangement;
+pub mod bass;
+pub mod cadence;
 pub mod composer;
+pub mod drums;
 pub mod generator;
+pub mod harmony;
+pub mod phrase;
 
 /// Gets random element from the vector.
 /// If vector is empty, returns
@@ -16,16 +26,18 @@ pub mod generator;
 /// ```
 /// use music_generator::midi::generator::random_from_vec;
 ///
+/// let mut rng = rand::thread_rng();
+///
 /// let mut v = vec![1];
-/// assert_eq!(random_from_vec(&mut v), Some(1));
+/// assert_eq!(random_from_vec(&mut v, &mut rng), Some(1));
 ///
 /// let mut empty = Vec::<u32>::new();
-/// assert_eq!(random_from_vec(&mut empty), None)
+/// assert_eq!(random_from_vec(&mut empty, &mut rng), None)
 /// ```
 
 #[inline]
-pub fn random_from_vec<T: Clone>(data: &mut Vec<T>) -> Option<T> {
-    data.shuffle(&mut rand::thread_rng());
+pub fn random_from_vec<T: Clone>(data: &mut Vec<T>, rng: &mut impl Rng) -> Option<T> {
+    data.shuffle(rng);
     data.first().map(|t| t.clone())
 }
 
@@ -54,46 +66,153 @@ pub fn pi_numbers(from: usize, len: usize) -> Vec<u32> {
 /// from PI with a given length
 
 #[inline]
-fn randomize_with_pi(len: usize) -> Vec<u32> {
-    pi_numbers(rand::thread_rng().gen::<usize>() % 50, len)
+fn randomize_with_pi(len: usize, rng: &mut impl Rng) -> Vec<u32> {
+    pi_numbers(rng.gen::<usize>() % 50, len)
+        .into_iter()
+        .collect()
+}
+
+/// Recalculates delays of the sequential notes
+/// as the time between the end of the previous note
+/// and the start of the current one
+
+#[inline]
+pub fn with_delays_from_starts(notes: Vec<NoteData>) -> Vec<NoteData> {
+    notes
+        .into_iter()
+        .scan(0, |prev_end, note| {
+            let delay = note.start().saturating_sub(*prev_end);
+            *prev_end = note.start() + note.length();
+            Some(note.clone_with_new_delay(delay))
+        })
+        .collect()
+}
+
+/// Recalculates starts of the sequential notes
+/// as the absolute positions from the beginning of the lead,
+/// summing delays and lengths of the previous notes
+
+#[inline]
+pub fn with_starts_from_delays(notes: Vec<NoteData>) -> Vec<NoteData> {
+    notes
+        .into_iter()
+        .scan(0, |prev_end, note| {
+            let start = *prev_end + note.delay();
+            *prev_end = start + note.length();
+            Some(note.clone_with_new_start(start))
+        })
+        .collect()
+}
+
+/// Joins single bar phrases (with starts relative to the bar)
+/// one after another: first note of every bar is delayed
+/// by the silence at the end of the previous bars and before the note itself.
+/// Empty bars stay silent
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     midi::{
+///         generator::{join_bars, with_starts_from_delays},
+///         time_signature::TimeSignature,
+///     },
+///     notes::{note::Note, note_data::NoteData},
+/// };
+///
+/// let note = |start| NoteData::new(Note::C4, 75, start, 128, start);
+/// let lead = join_bars(vec![vec![note(0)], Vec::new(), vec![note(128)]], TimeSignature::COMMON);
+///
+/// let starts = with_starts_from_delays(lead)
+///     .iter()
+///     .map(|note| note.start())
+///     .collect::<Vec<_>>();
+///
+/// assert_eq!(starts, vec![0, 2 * 512 + 128])
+/// ```
+
+#[inline]
+pub fn join_bars(bars: Vec<Vec<NoteData>>, time_signature: TimeSignature) -> Vec<NoteData> {
+    let mut delay = None;
+
+    bars.into_iter()
+        .flat_map(|mut bar| {
+            if let (Some(delay), Some(first_note)) = (delay, bar.first_mut()) {
+                *first_note = first_note.clone_with_new_delay(delay + first_note.start());
+            }
+
+            // Silence of the empty bar is added to the silence before it
+
+            delay = Some(match bar.is_empty() {
+                true => delay.unwrap_or(0) + time_signature.bar_time(),
+                false => time_before_bar_end(&bar, time_signature),
+            });
+
+            bar
+        })
+        .collect()
+}
+
+/// Time between the end of the last note of the bar and the end of the bar.
+/// Empty bar is silent for the whole time
+
+#[inline]
+pub fn time_before_bar_end(bar: &[NoteData], time_signature: TimeSignature) -> DeltaTime {
+    bar.last()
+        .map(|last_note| last_note.start() + last_note.length())
+        .map(|end| time_signature.bar_time().saturating_sub(end))
+        .unwrap_or(time_signature.bar_time())
+}
+
+/// Converts sequential notes with absolute starts
+/// (see [with_starts_from_delays]) back to the lead's form:
+/// delays are recalculated and starts become relative
+/// to the beginning of the bar
+
+#[inline]
+pub fn with_starts_in_bars(notes: Vec<NoteData>, time_signature: TimeSignature) -> Vec<NoteData> {
+    let bar_time = time_signature.bar_time();
+
+    with_delays_from_starts(notes)
         .into_iter()
+        .map(|note| note.clone_with_new_start(note.start() % bar_time))
         .collect()
 }
 
-/// Gets time for a given ratio in terms of bar's time.
-/// Note that bar is divided into 16 parts
+/// Gets time for a given number of 1/16 steps of the bar.
+/// Note that 4/4 bar is divided into 16 parts,
+/// other time signatures may have a different number of steps
 
 #[inline]
 fn get_bar_ratio(part: u32) -> DeltaTime {
-    part * 32
+    part * STEP_TIME
 }
 
 trait FixedToTempoNoteData {
-    fn with_fixed_to_tempo_length(self, lengths: &Vec<DeltaTime>) -> Self;
-    fn with_fixed_to_tempo_delay(self, delays: &Vec<DeltaTime>) -> Self;
+    fn with_fixed_to_tempo_length(self, lengths: &Vec<DeltaTime>, rng: &mut impl Rng) -> Self;
+    fn with_fixed_to_tempo_delay(self, delays: &Vec<DeltaTime>, rng: &mut impl Rng) -> Self;
 }
 
 impl FixedToTempoNoteData for NoteData {
     #[inline]
-    fn with_fixed_to_tempo_length(self, lengths: &Vec<DeltaTime>) -> Self {
+    fn with_fixed_to_tempo_length(self, lengths: &Vec<DeltaTime>, rng: &mut impl Rng) -> Self {
         self.clone_with_new_length(
             lengths
                 .iter()
                 .map(|&len| (len, (len as i32 - self.length() as i32).abs()))
                 .min_by_key(|(_, dif)| *dif)
-                .map(|(len, _)| len + randomize_with_pi(1)[0])
+                .map(|(len, _)| len + randomize_with_pi(1, rng)[0])
                 .unwrap_or(self.length()),
         )
     }
 
     #[inline]
-    fn with_fixed_to_tempo_delay(self, delays: &Vec<DeltaTime>) -> Self {
+    fn with_fixed_to_tempo_delay(self, delays: &Vec<DeltaTime>, rng: &mut impl Rng) -> Self {
         self.clone_with_new_delay(
             delays
                 .iter()
                 .map(|&delay| (delay, (delay as i32 - self.delay() as i32).abs()))
                 .min_by_key(|(_, dif)| *dif)
-                .map(|(delay, _)| delay + randomize_with_pi(1)[0])
+                .map(|(delay, _)| delay + randomize_with_pi(1, rng)[0])
                 .unwrap_or(self.delay()),
         )
     }
@@ -104,10 +223,15 @@ impl FixedToTempoNoteData for NoteData {
 /// generated from the BPM
 
 #[inline]
-fn fixed_to_tempo(note: NoteData, lengths: &Vec<DeltaTime>, delays: &Vec<DeltaTime>) -> NoteData {
+fn fixed_to_tempo(
+    note: NoteData,
+    lengths: &Vec<DeltaTime>,
+    delays: &Vec<DeltaTime>,
+    rng: &mut impl Rng,
+) -> NoteData {
     let fixed_velocity = std::cmp::min(60 + note.velocity(), 100);
 
-    note.with_fixed_to_tempo_length(lengths)
-        .with_fixed_to_tempo_delay(delays)
+    note.with_fixed_to_tempo_length(lengths, rng)
+        .with_fixed_to_tempo_delay(delays, rng)
         .clone_with_velocity(fixed_velocity)
 }
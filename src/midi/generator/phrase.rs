@@ -0,0 +1,298 @@
+use crate::{
+    midi::{
+        generator::with_delays_from_starts,
+        time_signature::{TimeSignature, STEP_TIME},
+    },
+    notes::{
+        note::Note,
+        note_data::{DeltaTime, NoteData},
+    },
+};
+
+use rand::Rng;
+
+/// Probability of every long enough note to receive the ornament
+const ORNAMENT_RATE: f64 = 0.5;
+
+/// Motivic development operations that derive new phrases from the existing one.
+/// Phrase is a single bar with starts relative to the bar.
+/// Transformed phrase stays on the 1/16 grid and inside the bar,
+/// its notes never overlap
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     midi::{generator::phrase::PhraseTransform, time_signature::TimeSignature},
+///     notes::{note::Note, note_data::NoteData},
+/// };
+///
+/// let scale = [60, 62, 64, 65, 67]
+///     .map(|midi: u8| Note::try_from(midi).unwrap())
+///     .to_vec();
+///
+/// let phrase = vec![
+///     NoteData::new(scale[0], 75, 0, 64, 0),
+///     NoteData::new(scale[2], 75, 64, 128, 0),
+/// ];
+///
+/// let mut rng = rand::thread_rng();
+/// let mut transform = |transform: PhraseTransform| {
+///     transform.apply(phrase.clone(), &scale, TimeSignature::COMMON, &mut rng)
+/// };
+///
+/// let sequence = transform(PhraseTransform::Sequence(1));
+/// assert_eq!(sequence[1].note(), scale[3]);
+///
+/// let retrograde = transform(PhraseTransform::Retrograde);
+/// assert_eq!(retrograde[0].note(), scale[2]);
+/// assert_eq!((retrograde[1].start(), retrograde[1].length()), (128, 64));
+///
+/// let augmentation = transform(PhraseTransform::Augmentation);
+/// assert_eq!((augmentation[1].start(), augmentation[1].length()), (128, 256))
+/// ```
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum PhraseTransform {
+    /// Every note is moved by the number of scale steps (up if positive)
+    Sequence(i8),
+
+    /// Intervals are mirrored around the first note on the scale
+    Inversion,
+
+    /// Notes are played backwards, rhythm is mirrored in the time span of the phrase
+    Retrograde,
+
+    /// Lengths and distances from the first note are doubled.
+    /// Notes that do not fit into the bar are dropped
+    Augmentation,
+
+    /// Lengths and distances from the first note are halved,
+    /// shortened phrase is repeated in the second half of the bar
+    Diminution,
+
+    /// Long notes start with the upper or the lower neighbour on the scale
+    Ornamentation,
+
+    /// Only the first half of the notes (at least one) is played
+    Truncation,
+}
+
+impl PhraseTransform {
+    /// Applies transform to the single bar phrase
+
+    #[inline]
+    pub fn apply(
+        &self,
+        phrase: Vec<NoteData>,
+        scale_notes: &Vec<Note>,
+        time_signature: TimeSignature,
+        rng: &mut impl Rng,
+    ) -> Vec<NoteData> {
+        let bar_time = time_signature.bar_time();
+
+        match self {
+            PhraseTransform::Sequence(steps) => move_on_scale(phrase, scale_notes, *steps as isize),
+            PhraseTransform::Inversion => invert(phrase, scale_notes),
+            PhraseTransform::Retrograde => reverse(phrase),
+            PhraseTransform::Augmentation => augment(phrase, bar_time),
+            PhraseTransform::Diminution => diminish(phrase, bar_time),
+            PhraseTransform::Ornamentation => ornament(phrase, scale_notes, rng),
+            PhraseTransform::Truncation => truncate(phrase),
+        }
+    }
+}
+
+/// Moves every note by the given number of steps on the scale.
+/// Notes that would leave the scale's range (or are not on the scale) keep their pitch
+
+#[inline]
+fn move_on_scale(phrase: Vec<NoteData>, scale_notes: &Vec<Note>, steps: isize) -> Vec<NoteData> {
+    phrase
+        .into_iter()
+        .map(|note| {
+            scale_position(&note, scale_notes)
+                .and_then(|pos| pos.checked_add_signed(steps))
+                .and_then(|pos| scale_notes.get(pos))
+                .map(|&moved| note.clone_with_new_note(moved))
+                .unwrap_or(note)
+        })
+        .collect()
+}
+
+/// Mirrors notes around the first one on the scale.
+/// Notes that would leave the scale's range keep their pitch
+
+#[inline]
+fn invert(phrase: Vec<NoteData>, scale_notes: &Vec<Note>) -> Vec<NoteData> {
+    let Some(axis) = phrase
+        .first()
+        .and_then(|first| scale_position(first, scale_notes))
+    else {
+        return phrase;
+    };
+
+    phrase
+        .into_iter()
+        .map(|note| {
+            scale_position(&note, scale_notes)
+                .and_then(|pos| (axis * 2).checked_sub(pos))
+                .and_then(|pos| scale_notes.get(pos))
+                .map(|&inverted| note.clone_with_new_note(inverted))
+                .unwrap_or(note)
+        })
+        .collect()
+}
+
+/// Plays notes backwards: every note ends
+/// where the mirrored one started in the original phrase
+
+#[inline]
+fn reverse(phrase: Vec<NoteData>) -> Vec<NoteData> {
+    let (Some(first), Some(last)) = (phrase.first(), phrase.last()) else {
+        return phrase;
+    };
+
+    let mirror = first.start() + end(last);
+
+    with_delays_from_starts(
+        phrase
+            .into_iter()
+            .rev()
+            .map(|note| note.clone_with_new_start(mirror - end(&note)))
+            .collect(),
+    )
+}
+
+/// Doubles lengths and distances from the first note
+
+#[inline]
+fn augment(phrase: Vec<NoteData>, bar_time: DeltaTime) -> Vec<NoteData> {
+    let first_start = phrase.first().map(|first| first.start()).unwrap_or(0);
+
+    fit_in_bar(
+        phrase
+            .into_iter()
+            .map(|note| {
+                note.clone_with_new_start(first_start + (note.start() - first_start) * 2)
+                    .clone_with_new_length(note.length() * 2)
+            })
+            .collect(),
+        bar_time,
+    )
+}
+
+/// Halves lengths and distances from the first note (keeping them on the grid)
+/// and repeats the shortened phrase in the second half of the bar
+
+#[inline]
+fn diminish(phrase: Vec<NoteData>, bar_time: DeltaTime) -> Vec<NoteData> {
+    let first_start = phrase.first().map(|first| first.start()).unwrap_or(0);
+    let half_bar = bar_time / 2 / STEP_TIME * STEP_TIME;
+    let half = |time: DeltaTime| time / 2 / STEP_TIME * STEP_TIME;
+
+    let diminished = phrase
+        .into_iter()
+        .map(|note| {
+            note.clone_with_new_start(first_start + half(note.start() - first_start))
+                .clone_with_new_length(half(note.length()).max(STEP_TIME))
+        })
+        .collect::<Vec<_>>();
+
+    let repeated = diminished
+        .iter()
+        .map(|note| note.clone_with_new_start(note.start() + half_bar))
+        .collect::<Vec<_>>();
+
+    fit_in_bar(diminished.into_iter().chain(repeated).collect(), bar_time)
+}
+
+/// Notes of at least two steps start with a step
+/// of the upper or the lower neighbour on the scale
+
+#[inline]
+fn ornament(phrase: Vec<NoteData>, scale_notes: &Vec<Note>, rng: &mut impl Rng) -> Vec<NoteData> {
+    let ornamented = phrase
+        .into_iter()
+        .flat_map(|note| {
+            let neighbour = scale_position(&note, scale_notes).and_then(|pos| {
+                let (first, second) = match rng.gen_bool(0.5) {
+                    true => (pos.checked_add(1), pos.checked_sub(1)),
+                    false => (pos.checked_sub(1), pos.checked_add(1)),
+                };
+
+                first
+                    .and_then(|pos| scale_notes.get(pos))
+                    .or_else(|| second.and_then(|pos| scale_notes.get(pos)))
+            });
+
+            match neighbour {
+                Some(&neighbour)
+                    if note.length() >= STEP_TIME * 2 && rng.gen_bool(ORNAMENT_RATE) =>
+                {
+                    vec![
+                        note.clone_with_new_note(neighbour)
+                            .clone_with_new_length(STEP_TIME),
+                        note.clone_with_new_start(note.start() + STEP_TIME)
+                            .clone_with_new_length(note.length() - STEP_TIME),
+                    ]
+                }
+
+                _ => vec![note],
+            }
+        })
+        .collect();
+
+    with_delays_from_starts(ornamented)
+}
+
+/// Keeps the first half of the notes (at least one)
+
+#[inline]
+fn truncate(phrase: Vec<NoteData>) -> Vec<NoteData> {
+    let len = phrase.len().div_ceil(2);
+    phrase.into_iter().take(len).collect()
+}
+
+/// Sorts notes by their starts, removes notes with the same start
+/// or outside the bar and shortens notes to end
+/// before the next one and inside the bar
+
+#[inline]
+fn fit_in_bar(mut notes: Vec<NoteData>, bar_time: DeltaTime) -> Vec<NoteData> {
+    notes.retain(|note| note.start() < bar_time);
+    notes.sort_by_key(|note| note.start());
+    notes.dedup_by_key(|note| note.start());
+
+    let next_starts = notes
+        .iter()
+        .skip(1)
+        .map(|next| next.start())
+        .chain(std::iter::once(bar_time))
+        .collect::<Vec<_>>();
+
+    with_delays_from_starts(
+        notes
+            .into_iter()
+            .zip(next_starts)
+            .map(|(note, next_start)| {
+                note.clone_with_new_length(note.length().min(next_start - note.start()))
+            })
+            .collect(),
+    )
+}
+
+/// Position of the note's pitch in the scale
+
+#[inline]
+fn scale_position(note: &NoteData, scale_notes: &[Note]) -> Option<usize> {
+    scale_notes
+        .iter()
+        .position(|&scale_note| scale_note == note.note())
+}
+
+/// Time when the note ends
+
+#[inline]
+fn end(note: &NoteData) -> DeltaTime {
+    note.start() + note.length()
+}
@@ -1,13 +1,23 @@
-use crate::melody_type::SynthwaveMelodyType;
+use crate::{
+    error::{Error, Result},
+    melody_type::SynthwaveMelodyType,
+    notes::note::Note,
+};
 
 use rust_music_theory::{
-    note::PitchClass,
-    scale::{Mode, ScaleType},
+    note::{Notes, PitchClass},
+    scale::{Direction, Mode, Scale, ScaleType},
 };
 
+use itertools::Itertools;
+use std::ops::RangeInclusive;
+
 pub mod bpm;
 pub mod generator;
 pub mod parser;
+pub mod resolution;
+pub mod smf;
+pub mod time_signature;
 
 #[inline]
 pub fn key_list() -> Vec<PitchClass> {
@@ -51,11 +61,54 @@ pub fn mode_list() -> Vec<Mode> {
     ]
 }
 
+/// Named forms of the melody. User-defined forms
+/// (e.g. `AAB'A`) are parsed with [SynthwaveMelodyType]'s `FromStr`
+
 #[inline]
 pub fn melody_types() -> Vec<SynthwaveMelodyType> {
     vec![
         SynthwaveMelodyType::ABAB,
         SynthwaveMelodyType::AAAB,
         SynthwaveMelodyType::ABAC,
+        SynthwaveMelodyType::AABA,
+        SynthwaveMelodyType::ABCD,
+        SynthwaveMelodyType::AABB,
+        SynthwaveMelodyType::CallAndResponse,
+        SynthwaveMelodyType::ABABCadence,
     ]
 }
+
+/// Picks all notes of the scale in the given octaves.
+/// These notes help to construct
+/// both lead melody and chords in harmony.
+/// Returns error if scale can not be constructed
+/// or its notes are out of the supported range
+///
+/// # Example
+/// ```
+/// use music_generator::midi::scale_notes;
+/// use rust_music_theory::{note::PitchClass, scale::{Mode, ScaleType}};
+///
+/// let notes = scale_notes(PitchClass::A, ScaleType::Diatonic, Mode::Aeolian, 4..=5).unwrap();
+/// assert_eq!(notes.len(), 16)
+/// ```
+
+#[inline]
+pub fn scale_notes(
+    key: PitchClass,
+    scale: ScaleType,
+    mode: Mode,
+    octaves: RangeInclusive<u8>,
+) -> Result<Vec<Note>> {
+    octaves
+        .map(|octave| {
+            Scale::new(scale, key, octave, Some(mode), Direction::Ascending)
+                .map_err(|err| Error::InvalidScale(err.to_string()))?
+                .notes()
+                .into_iter()
+                .map(Note::try_from)
+                .collect::<Result<Vec<_>>>()
+        })
+        .flatten_ok()
+        .collect()
+}
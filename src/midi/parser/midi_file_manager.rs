@@ -1,40 +1,56 @@
-use crate::{midi::parser::midi_parser::MidiParser, notes::note_data::NoteData};
-use ghakuf::reader::Reader;
+use crate::{
+    error::{Error, Result},
+    midi::parser::midi_parser::MidiParser,
+    notes::note_data::NoteData,
+};
+use ghakuf::reader::{ReadError, Reader};
+use itertools::Itertools;
 
 use std::{
     fs::DirEntry,
     path::{Path, PathBuf},
 };
 
-const MIDI_PATH: &str = "./genetic_samples";
+/// Folder with the sample files of the genetic algorithm
+pub const DEFAULT_SAMPLES_PATH: &str = "./genetic_samples";
 
-/// Extracts all notes from the `midi` folder from all files.
+/// Extracts all notes from all files of the given folder.
+/// Folder is created if it does not exist.
 /// Notes are parsed from the MIDI events in files.
-/// It is assumed that all files in `midi` folder are `*.mid` files.
+/// It is assumed that all files in the folder are `*.mid` files.
 /// Fetching and scanning is implemented asynchronously with
 /// native polling mechanism (epoll for Linux, kqueue for FreeBSD),
-/// using [monoio] crate
+/// using [monoio] crate.
+/// Files that can not be read or contain malformed
+/// note events are skipped, so a single broken sample
+/// does not stop the generation. Their errors are returned
+/// together with the notes of the parsed files
 
 #[inline]
-pub async fn extract_notes() -> Result<Vec<(PathBuf, Vec<NoteData>)>, Box<dyn std::error::Error>> {
-    let lead_path = Path::new(MIDI_PATH);
-
+pub async fn extract_notes(
+    lead_path: &Path,
+) -> Result<(Vec<(PathBuf, Vec<NoteData>)>, Vec<Error>)> {
     if !lead_path.exists() {
-        std::fs::create_dir(lead_path)?;
+        std::fs::create_dir_all(lead_path)?;
     }
 
-    // Walks through the `midi` directory and scans all files asynchronously
+    // Files are sorted to keep the same order
+    // of samples for the seeded generation
 
-    let tasks = std::fs::read_dir(lead_path)?
-        .into_iter()
+    let files = std::fs::read_dir(lead_path)?
         .map(to_file_opt)
+        .sorted()
+        .collect::<Vec<_>>();
+
+    // Walks through the samples directory and scans all files asynchronously
+
+    let tasks = files
+        .into_iter()
         .map(|file_opt| {
             monoio::spawn(async {
                 file_opt.map(|lead_file| {
-                    let mut midi_parser = MidiParser::new();
-                    let mut midi_reader = Reader::new(&mut midi_parser, &*lead_file).unwrap();
-                    midi_reader.read().unwrap();
-                    (lead_file, midi_parser.extract_notes())
+                    let notes = parse_file(&lead_file);
+                    (lead_file, notes)
                 })
             })
         })
@@ -43,14 +59,50 @@ pub async fn extract_notes() -> Result<Vec<(PathBuf, Vec<NoteData>)>, Box<dyn st
     // Awaits all tasks and collects all data
 
     let mut leads_vec = Vec::with_capacity(tasks.len());
+    let mut skipped = Vec::new();
 
     for task in tasks {
-        if let Some(lead) = monoio::join!(task).0 {
-            leads_vec.push(lead)
+        match monoio::join!(task).0 {
+            Some((lead_file, Ok(notes))) => leads_vec.push((lead_file, notes)),
+            Some((_, Err(err))) => skipped.push(err),
+            None => {}
         }
     }
 
-    Ok(leads_vec)
+    Ok((leads_vec, skipped))
+}
+
+/// Reads and parses a single MIDI file
+
+#[inline]
+pub fn parse_file(path: &Path) -> Result<Vec<NoteData>> {
+    let to_midi_error = |err: ReadError| Error::Midi {
+        path: path.to_path_buf(),
+        message: read_error_message(&err),
+    };
+
+    let mut midi_parser = MidiParser::new();
+    let mut midi_reader = Reader::new(&mut midi_parser, path).map_err(to_midi_error)?;
+    midi_reader.read().map_err(to_midi_error)?;
+    midi_parser.extract_notes()
+}
+
+/// Describes the reader's error.
+/// [ReadError]'s own [std::fmt::Display] implementation
+/// panics for the files opened by the reader, so it is not used
+
+#[inline]
+fn read_error_message(err: &ReadError) -> String {
+    match err {
+        ReadError::InvalidHeaderTag { tag, .. } => format!("Invalid header tag {:?}", tag),
+        ReadError::InvalidIdentifyCode { code, .. } => format!("Invalid identify code {code}"),
+        ReadError::InvalidTrackTag { tag, .. } => format!("Invalid track tag {:?}", tag),
+        ReadError::Io(err) => err.to_string(),
+        ReadError::NoValidHandler => String::from("No valid handler"),
+        ReadError::UnknownMessageStatus { status, .. } => {
+            format!("Unknown message status {status}")
+        }
+    }
 }
 
 #[inline]
@@ -1,21 +1,31 @@
-use crate::notes::{note::Note, note_data::*};
-
-use ghakuf::{
-    messages::{MetaEvent, MidiEvent},
-    reader::Handler,
+use crate::{
+    error::{Error, Result},
+    midi::resolution::Resolution,
+    notes::{note::Note, note_data::*},
 };
 
+use ghakuf::{messages::MidiEvent, reader::Handler};
+
 use itertools::Itertools;
 use std::collections::{BTreeMap, HashMap};
 
 /// Parses a single .mid file and converts
-/// all events from it to the [NoteData]
+/// all events from it to the [NoteData].
+/// Time of the notes is rescaled from the file's
+/// time base to the [Resolution::GENERATION].
+/// Notes out of the supported range (e.g. low drums or bass)
+/// are skipped, the rest of the file is still parsed.
+/// The first malformed event is remembered
+/// and returned from [MidiParser::extract_notes]
 
 #[derive(Debug, Default)]
 pub struct MidiParser {
     notes: BTreeMap<Note, Vec<(Velocity, DeltaTime, DeltaTime, DeltaTime)>>,
     delta_timer: DeltaTime,
     notes_on_hash: HashMap<Note, (Velocity, DeltaTime, DeltaTime)>,
+    merged_notes_on: HashMap<Note, usize>,
+    time_base: Resolution,
+    error: Option<Error>,
 }
 
 impl MidiParser {
@@ -27,86 +37,119 @@ impl MidiParser {
             notes: BTreeMap::new(),
             delta_timer: 0,
             notes_on_hash: HashMap::new(),
+            merged_notes_on: HashMap::new(),
+            time_base: Resolution::GENERATION,
+            error: None,
         }
     }
 
-    /// Extracts all parsed notes after scanning was done
+    /// Extracts all parsed notes after scanning was done.
+    /// Notes are rescaled to the [Resolution::GENERATION].
+    /// Returns error if the file contained malformed note events
 
     #[inline]
-    pub fn extract_notes(self) -> Vec<NoteData> {
-        self.notes
+    pub fn extract_notes(self) -> Result<Vec<NoteData>> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        let time_base = self.time_base;
+
+        Ok(self
+            .notes
             .into_iter()
             .map(|(note, plays)| {
                 plays
                     .into_iter()
                     .map(|(vel, start, len, delay)| NoteData::new(note, vel, start, len, delay))
+                    .map(|nd| nd.clone_rescaled(time_base, Resolution::GENERATION))
                     .collect::<Vec<_>>()
             })
             .flatten()
             .sorted()
-            .collect()
+            .collect())
     }
-}
 
-impl Handler for MidiParser {
+    /// Remembers the first error occurred during the parsing
+
     #[inline]
-    fn header(&mut self, format: u16, track: u16, time_base: u16) {
-        println!("HEADER; FORMAT: {format} TRACK {track} TIME BASE {time_base}");
+    fn set_error(&mut self, err: Error) {
+        self.error.get_or_insert(err);
     }
 
+    /// Adds note to the map of current on notes.
+    /// Note that is already on is merged into the first one,
+    /// its note off event is remembered to be skipped.
+    /// Notes out of the supported range are skipped
+
     #[inline]
-    fn meta_event(&mut self, delta_time: u32, event: &MetaEvent, data: &Vec<u8>) {
-        println!(
-            "META; DELTA {delta_time} EVENT: {} DATA: {:?}",
-            *event, *data
-        );
+    fn note_on(&mut self, midi: u8, velocity: Velocity, delta_time: DeltaTime) {
+        match Note::try_from(midi) {
+            Ok(note) if self.notes_on_hash.contains_key(&note) => {
+                *self.merged_notes_on.entry(note).or_insert(0) += 1;
+            }
+
+            Ok(note) => {
+                self.notes_on_hash
+                    .insert(note, (velocity, self.delta_timer, delta_time));
+            }
+
+            Err(_) => {}
+        }
     }
 
-    #[inline]
-    fn midi_event(&mut self, delta_time: u32, event: &MidiEvent) {
-        // Increases whole file's timer
-        // to construct start in NoteData
+    /// Picks and removes entry with the note,
+    /// finishing the construction of the NoteData.
+    /// Extra note off events of the merged overlapping notes
+    /// and of the notes out of the supported range are skipped,
+    /// other unmatched ones are reported
 
-        self.delta_timer += delta_time;
-        println!("MIDI; DELTA: {delta_time}, event: {}", *event);
+    #[inline]
+    fn note_off(&mut self, midi: u8) {
+        let Ok(note) = Note::try_from(midi) else {
+            return;
+        };
 
-        match event {
-            MidiEvent::NoteOn {
-                ch: _ch,
-                note,
-                velocity,
-            } => {
-                // Adds note to the map of current on notes
+        let Some((vel, start, delay)) = self.notes_on_hash.remove(&note) else {
+            return match self.merged_notes_on.get_mut(&note) {
+                Some(merged) if *merged > 0 => *merged -= 1,
+                _ => self.set_error(Error::UnmatchedNoteOff(midi)),
+            };
+        };
 
-                let note = Note::from(*note);
+        // Inserts new note data to the tree map,
+        // that sorts all notes in in according to the pitch
 
-                self.notes_on_hash
-                    .entry(note)
-                    .or_insert((*velocity, self.delta_timer, delta_time));
-            }
+        let note_len = self.delta_timer - start;
 
-            MidiEvent::NoteOff {
-                ch: _ch,
-                note,
-                velocity: _velocity,
-            } => {
-                // Picks and removes entry with the note,
-                // finishing the construction of the NoteData
+        self.notes
+            .entry(note)
+            .or_insert(Vec::new())
+            .push((vel, start, note_len, delay))
+    }
+}
 
-                let note = Note::from(*note);
-                let (vel, start, delay) = self.notes_on_hash.remove(&note).unwrap();
+impl Handler for MidiParser {
+    #[inline]
+    fn header(&mut self, _format: u16, _track: u16, time_base: u16) {
+        // SMPTE time bases are not supported,
+        // such files are read without rescaling
 
-                // Inserts new note data to the tree map,
-                // that sorts all notes in in according to the pitch
+        self.time_base = Resolution::new(time_base).unwrap_or_default();
+    }
 
-                let note_len = self.delta_timer - start;
+    #[inline]
+    fn midi_event(&mut self, delta_time: u32, event: &MidiEvent) {
+        // Increases whole file's timer
+        // to construct start in NoteData
 
-                self.notes
-                    .entry(note)
-                    .or_insert(Vec::new())
-                    .push((vel, start, note_len, delay))
-            }
+        self.delta_timer += delta_time;
 
+        match event {
+            // Note on with zero velocity is the same as note off
+            MidiEvent::NoteOn { note, velocity, .. } if *velocity == 0 => self.note_off(*note),
+            MidiEvent::NoteOn { note, velocity, .. } => self.note_on(*note, *velocity, delta_time),
+            MidiEvent::NoteOff { note, .. } => self.note_off(*note),
             _ => {}
         }
     }
@@ -0,0 +1,93 @@
+use crate::notes::note_data::DeltaTime;
+
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// Resolution (time base) of the MIDI file
+/// in ticks per quarter note (PPQ).
+/// All notes are generated in [Resolution::GENERATION]
+/// and rescaled to the resolution of the output file.
+/// Parsed files are rescaled from their own resolution
+/// to [Resolution::GENERATION]
+///
+/// # Example
+/// ```
+/// use music_generator::midi::resolution::Resolution;
+///
+/// let resolution = Resolution::new(480).unwrap();
+/// assert_eq!(resolution.step_time(), 120);
+/// assert_eq!(Resolution::GENERATION.rescale(32, resolution), 120);
+/// assert_eq!(resolution.rescale(960, Resolution::GENERATION), 256)
+/// ```
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Resolution {
+    ppq: u16,
+}
+
+impl Resolution {
+    /// Resolution of the generated notes (128 ticks per quarter)
+    pub const GENERATION: Self = Self { ppq: 128 };
+
+    /// Constructs resolution with the given ticks per quarter.
+    /// PPQ has to be in range 1..=32767
+    /// (greater values are reserved for the SMPTE time base)
+
+    #[inline]
+    pub fn new(ppq: u16) -> Option<Self> {
+        match (1..=0x7FFF).contains(&ppq) {
+            true => Some(Self { ppq }),
+            false => None,
+        }
+    }
+
+    /// Number of ticks per quarter note
+
+    #[inline]
+    pub const fn ppq(&self) -> u16 {
+        self.ppq
+    }
+
+    /// Number of ticks in a single 1/16 note
+
+    #[inline]
+    pub const fn step_time(&self) -> DeltaTime {
+        self.ppq as DeltaTime / 4
+    }
+
+    /// Converts time from this resolution to the given one.
+    /// Result is rounded to the closest tick
+
+    #[inline]
+    pub fn rescale(&self, time: DeltaTime, to: Resolution) -> DeltaTime {
+        let (from, to) = (self.ppq as u64, to.ppq as u64);
+        ((time as u64 * to + from / 2) / from) as DeltaTime
+    }
+}
+
+impl Default for Resolution {
+    #[inline]
+    fn default() -> Self {
+        Self::GENERATION
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = ();
+
+    /// Parses number of ticks per quarter
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ppq = s.trim().parse().map_err(|_| ())?;
+        Self::new(ppq).ok_or(())
+    }
+}
+
+impl Display for Resolution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.ppq)
+    }
+}
@@ -0,0 +1,95 @@
+use crate::midi::resolution::Resolution;
+use ghakuf::messages::Message;
+
+const HEADER_TAG: &[u8; 4] = b"MThd";
+const TRACK_TAG: &[u8; 4] = b"MTrk";
+const HEADER_LENGTH: u32 = 6;
+
+/// Multiple tracks that are played simultaneously
+const FORMAT: u16 = 1;
+
+/// Serializes tracks to the Standard MIDI File (format 1).
+/// Every track is a list of messages without [Message::TrackChange] events,
+/// end of the track event has to be already included.
+/// Running status is used for the consecutive MIDI events
+/// with the same status byte
+///
+/// # Example
+/// ```
+/// use ghakuf::messages::{Message, MidiEvent};
+/// use music_generator::midi::{resolution::Resolution, smf::to_smf_bytes};
+///
+/// let note_on = |note| Message::MidiEvent {
+///     delta_time: 0,
+///     event: MidiEvent::NoteOn { ch: 0, note, velocity: 80 },
+/// };
+///
+/// let smf = to_smf_bytes(&vec![vec![note_on(60), note_on(64)]], Resolution::GENERATION);
+/// assert_eq!(&smf[0..4], b"MThd");
+/// assert_eq!(&smf[14..22], b"MTrk\x00\x00\x00\x07");
+/// assert_eq!(&smf[22..], [0x00, 0x90, 60, 80, 0x00, 64, 80])
+/// ```
+
+#[inline]
+pub fn to_smf_bytes(tracks: &Vec<Vec<Message>>, resolution: Resolution) -> Vec<u8> {
+    let mut smf = Vec::new();
+
+    smf.extend_from_slice(HEADER_TAG);
+    smf.extend_from_slice(&HEADER_LENGTH.to_be_bytes());
+    smf.extend_from_slice(&FORMAT.to_be_bytes());
+    smf.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+    smf.extend_from_slice(&resolution.ppq().to_be_bytes());
+
+    tracks
+        .iter()
+        .map(track_chunk)
+        .for_each(|chunk| smf.extend(chunk));
+
+    smf
+}
+
+/// Serializes track's messages to the track chunk with its length.
+/// Status byte of the MIDI event is omitted if it is the same
+/// as the previous one. Meta and SysEx events cancel the running status
+
+#[inline]
+fn track_chunk(messages: &Vec<Message>) -> Vec<u8> {
+    let data = messages
+        .iter()
+        .scan(None, |running_status: &mut Option<u8>, message| {
+            let mut binary = message.binary();
+
+            match message {
+                Message::MidiEvent { .. } => {
+                    // Delta time is written as the variable length quantity,
+                    // its last byte is the only one without the highest bit
+
+                    let status_pos = binary
+                        .iter()
+                        .position(|byte| byte & 0x80 == 0)
+                        .map(|pos| pos + 1)
+                        .unwrap_or(0);
+
+                    let status = binary[status_pos];
+
+                    if *running_status == Some(status) {
+                        binary.remove(status_pos);
+                    }
+
+                    *running_status = Some(status);
+                }
+
+                _ => *running_status = None,
+            }
+
+            Some(binary)
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+
+    let mut chunk = Vec::with_capacity(data.len() + 8);
+    chunk.extend_from_slice(TRACK_TAG);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend(data);
+    chunk
+}
@@ -0,0 +1,161 @@
+use crate::{midi::resolution::Resolution, notes::note_data::DeltaTime};
+
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// Time of a single 1/16 step of the bar
+/// in the resolution of the generated notes
+pub const STEP_TIME: DeltaTime = Resolution::GENERATION.step_time();
+
+/// Time signature of the melody.
+/// All bars are measured in 1/16 steps,
+/// so 4/4 bar contains 16 steps, 3/4 - 12, 7/8 - 14, etc.
+///
+/// # Example
+/// ```
+/// use music_generator::midi::time_signature::TimeSignature;
+///
+/// let signature = "7/8".parse::<TimeSignature>().unwrap();
+/// assert_eq!(signature.steps_per_bar(), 14);
+/// assert_eq!(signature.beat_positions(), vec![0, 4, 8]);
+/// assert_eq!(TimeSignature::COMMON.bar_time(), 512)
+/// ```
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TimeSignature {
+    numerator: u8,
+    denominator: u8,
+}
+
+impl TimeSignature {
+    /// Common time (4/4)
+    pub const COMMON: Self = Self {
+        numerator: 4,
+        denominator: 4,
+    };
+
+    /// Constructs time signature. Numerator has to be in range 2..=16,
+    /// denominator is either 2, 4 or 8. Otherwise, returns [None]
+
+    #[inline]
+    pub fn new(numerator: u8, denominator: u8) -> Option<Self> {
+        match (2..=16).contains(&numerator) && [2, 4, 8].contains(&denominator) {
+            true => Some(Self {
+                numerator,
+                denominator,
+            }),
+
+            false => None,
+        }
+    }
+
+    /// Number of beats (or counts) in the bar
+
+    #[inline]
+    pub fn numerator(&self) -> u8 {
+        self.numerator
+    }
+
+    /// Note value of a single count
+
+    #[inline]
+    pub fn denominator(&self) -> u8 {
+        self.denominator
+    }
+
+    /// Number of 1/16 steps in the bar
+
+    #[inline]
+    pub fn steps_per_bar(&self) -> u32 {
+        self.numerator as u32 * 16 / self.denominator as u32
+    }
+
+    /// Time of the whole bar
+
+    #[inline]
+    pub fn bar_time(&self) -> DeltaTime {
+        self.steps_per_bar() * STEP_TIME
+    }
+
+    /// Positions (in 1/16 steps) of the beats in the bar.
+    /// For the quarter and half counts every count is a beat.
+    /// Compound meters (6/8, 9/8, 12/8) are beaten in dotted quarters.
+    /// Other eighth meters are grouped by two eighths,
+    /// and the last group takes three of them (7/8 is 2 + 2 + 3)
+
+    #[inline]
+    pub fn beat_positions(&self) -> Vec<u32> {
+        let count_steps = 16 / self.denominator as u32;
+        let counts = self.numerator as u32;
+
+        match (self.denominator, counts % 3) {
+            (8, 0) => (0..counts)
+                .step_by(3)
+                .map(|count| count * count_steps)
+                .collect(),
+            (8, _) => (0..counts - counts % 2 - 1)
+                .step_by(2)
+                .map(|count| count * count_steps)
+                .collect(),
+            _ => (0..counts).map(|count| count * count_steps).collect(),
+        }
+    }
+
+    /// Position of the step relative to the beat it belongs to
+
+    #[inline]
+    pub fn position_in_beat(&self, step: u32) -> u32 {
+        self.beat_positions()
+            .into_iter()
+            .rev()
+            .find(|&beat| beat <= step)
+            .map(|beat| step - beat)
+            .unwrap_or(step)
+    }
+
+    /// Data of the time signature meta event:
+    /// numerator, denominator as the power of 2,
+    /// MIDI clocks per beat and 1/32 notes per quarter
+
+    #[inline]
+    pub fn meta_event_data(&self) -> Vec<u8> {
+        let beats = self.beat_positions();
+        let beat_steps = beats.get(1).copied().unwrap_or(self.steps_per_bar());
+
+        vec![
+            self.numerator,
+            self.denominator.trailing_zeros() as u8,
+            (beat_steps * 6) as u8,
+            8,
+        ]
+    }
+}
+
+impl Default for TimeSignature {
+    #[inline]
+    fn default() -> Self {
+        Self::COMMON
+    }
+}
+
+impl FromStr for TimeSignature {
+    type Err = ();
+
+    /// Parses time signature written as `numerator/denominator`
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (numerator, denominator) = s.trim().split_once('/').ok_or(())?;
+        let numerator = numerator.trim().parse().map_err(|_| ())?;
+        let denominator = denominator.trim().parse().map_err(|_| ())?;
+        Self::new(numerator, denominator).ok_or(())
+    }
+}
+
+impl Display for TimeSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::Error;
 use rust_music_theory::note::{Note as MTNote, PitchClass};
 use std::{cmp::Ordering, iter::Zip, ops::RangeFrom, ops::Sub};
 use strum::IntoEnumIterator;
@@ -279,36 +280,55 @@ impl Note {
     }
 }
 
-impl From<u8> for Note {
+impl TryFrom<u8> for Note {
+    type Error = Error;
+
     /// Constructs the note from the given midi value.
     /// Value have to be in range (21..=128)
+    ///
+    /// # Example
+    /// ```
+    /// use music_generator::notes::note::Note;
+    /// assert_eq!(Note::try_from(69).ok(), Some(Note::A4));
+    /// assert!(Note::try_from(10).is_err())
+    /// ```
 
     #[inline]
-    fn from(value: u8) -> Self {
-        Self::from_midi_or_none(value).expect("Illegal MIDI value when converting from u8 to Note")
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Self::from_midi_or_none(value).ok_or(Error::InvalidNote(value))
     }
 }
 
-impl From<MTNote> for Note {
+impl TryFrom<MTNote> for Note {
+    type Error = Error;
+
+    /// Constructs the note from the pitch class and the octave.
+    /// Note has to be in range A0..=Ab9
+
     #[inline]
-    fn from(value: MTNote) -> Self {
-        Self::from(
-            (value.octave + 1) * 12
-                + match value.pitch_class {
-                    PitchClass::C => 0,
-                    PitchClass::Cs => 1,
-                    PitchClass::D => 2,
-                    PitchClass::Ds => 3,
-                    PitchClass::E => 4,
-                    PitchClass::F => 5,
-                    PitchClass::Fs => 6,
-                    PitchClass::G => 7,
-                    PitchClass::Gs => 8,
-                    PitchClass::A => 9,
-                    PitchClass::As => 10,
-                    PitchClass::B => 11,
-                },
-        )
+    fn try_from(value: MTNote) -> Result<Self, Self::Error> {
+        let pitch = match value.pitch_class {
+            PitchClass::C => 0,
+            PitchClass::Cs => 1,
+            PitchClass::D => 2,
+            PitchClass::Ds => 3,
+            PitchClass::E => 4,
+            PitchClass::F => 5,
+            PitchClass::Fs => 6,
+            PitchClass::G => 7,
+            PitchClass::Gs => 8,
+            PitchClass::A => 9,
+            PitchClass::As => 10,
+            PitchClass::B => 11,
+        };
+
+        value
+            .octave
+            .checked_add(1)
+            .and_then(|octave| octave.checked_mul(12))
+            .and_then(|midi| midi.checked_add(pitch))
+            .and_then(Self::from_midi_or_none)
+            .ok_or(Error::InvalidOctave(value.octave))
     }
 }
 
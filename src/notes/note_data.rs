@@ -1,4 +1,4 @@
-use crate::notes::note::Note;
+use crate::{midi::resolution::Resolution, notes::note::Note};
 use ghakuf::messages::{Message, MidiEvent};
 use std::cmp::Ordering;
 
@@ -159,6 +159,20 @@ impl NoteData {
         Self::new(self.note, self.velocity, self.start, self.length, delay)
     }
 
+    /// Clones the data with start, length and delay
+    /// converted from one resolution to another
+
+    #[inline]
+    pub fn clone_rescaled(&self, from: Resolution, to: Resolution) -> Self {
+        Self::new(
+            self.note,
+            self.velocity,
+            from.rescale(self.start, to),
+            from.rescale(self.length, to),
+            from.rescale(self.delay, to),
+        )
+    }
+
     /// Increases note's pitch with the given number of semitones
 
     #[inline]
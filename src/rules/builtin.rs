@@ -0,0 +1,287 @@
+use crate::{
+    midi::{
+        generator::cadence::{bar_endings, degree_pitch_class, CadencePlan},
+        time_signature::{TimeSignature, STEP_TIME},
+    },
+    notes::{note::Note, note_data::NoteData},
+    rules::MelodyRule,
+    WithNextIterable,
+};
+
+use itertools::Itertools;
+use rust_music_theory::note::PitchClass;
+
+/// Same note cannot repeat more than the given number of times in a row
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct NoteRepetition {
+    pub max_repetitions: usize,
+}
+
+impl Default for NoteRepetition {
+    #[inline]
+    fn default() -> Self {
+        Self { max_repetitions: 2 }
+    }
+}
+
+impl MelodyRule for NoteRepetition {
+    #[inline]
+    fn name(&self) -> &str {
+        "repetition"
+    }
+
+    #[inline]
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn violation(&self, lead: &Vec<NoteData>) -> Option<String> {
+        lead.iter()
+            .map(|note| note.note())
+            .dedup_with_count()
+            .find(|(count, _)| *count > self.max_repetitions)
+            .map(|(count, note)| {
+                format!(
+                    "{note:?} repeats {count} times in a row, at most {} are allowed",
+                    self.max_repetitions
+                )
+            })
+    }
+}
+
+/// Distance between the neighbour notes in semitones is less than the limit
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LeapLimit {
+    pub limit: u8,
+}
+
+impl Default for LeapLimit {
+    #[inline]
+    fn default() -> Self {
+        Self { limit: 7 }
+    }
+}
+
+impl MelodyRule for LeapLimit {
+    #[inline]
+    fn name(&self) -> &str {
+        "leap"
+    }
+
+    #[inline]
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn violation(&self, lead: &Vec<NoteData>) -> Option<String> {
+        lead.with_next()
+            .map(|(next, prev)| (prev.note(), next.note()))
+            .find(|(prev, next)| next.midi().abs_diff(prev.midi()) >= self.limit)
+            .map(|(prev, next)| {
+                format!(
+                    "leap from {prev:?} to {next:?} is {} semitones, it has to be less than {}",
+                    next.midi().abs_diff(prev.midi()),
+                    self.limit
+                )
+            })
+    }
+}
+
+/// Parts of notes without pauses between them
+/// are not longer than the given number of notes
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LegatoRunLimit {
+    pub max_notes: usize,
+}
+
+impl Default for LegatoRunLimit {
+    #[inline]
+    fn default() -> Self {
+        Self { max_notes: 6 }
+    }
+}
+
+impl MelodyRule for LegatoRunLimit {
+    #[inline]
+    fn name(&self) -> &str {
+        "legato_run"
+    }
+
+    #[inline]
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn violation(&self, lead: &Vec<NoteData>) -> Option<String> {
+        // Part starts with the note before the first zero delay
+
+        lead.iter()
+            .skip(1)
+            .map(|note| note.delay() == 0)
+            .dedup_with_count()
+            .find(|&(count, is_zero)| is_zero && count >= self.max_notes)
+            .map(|(count, _)| {
+                format!(
+                    "{} notes go without pauses, at most {} are allowed",
+                    count + 1,
+                    self.max_notes
+                )
+            })
+    }
+}
+
+/// Notes with start and the delay both equal to the odd number of 1/16 steps
+/// are not allowed. | |N|?|?| - such 1/4 of bars are not allowed
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct OddRhythm;
+
+impl MelodyRule for OddRhythm {
+    #[inline]
+    fn name(&self) -> &str {
+        "odd_rhythm"
+    }
+
+    #[inline]
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn violation(&self, lead: &Vec<NoteData>) -> Option<String> {
+        lead.iter()
+            .position(|note| note.start() / STEP_TIME % 2 == 1 && note.delay() / STEP_TIME % 2 == 1)
+            .map(|i| format!("note {i} has both start and delay on the odd 1/16 step"))
+    }
+}
+
+/// At least the given number of notes go
+/// right after the previous ones without any pause
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MinLegato {
+    pub min_notes: usize,
+}
+
+impl Default for MinLegato {
+    #[inline]
+    fn default() -> Self {
+        Self { min_notes: 1 }
+    }
+}
+
+impl MelodyRule for MinLegato {
+    #[inline]
+    fn name(&self) -> &str {
+        "legato"
+    }
+
+    #[inline]
+    fn violation(&self, lead: &Vec<NoteData>) -> Option<String> {
+        let legato_notes = lead.iter().filter(|note| note.delay() == 0).count();
+
+        match legato_notes < self.min_notes {
+            true => Some(format!(
+                "{legato_notes} notes go without pause, at least {} are required",
+                self.min_notes
+            )),
+
+            false => None,
+        }
+    }
+}
+
+/// Number of pauses is not greater than the given one
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PauseLimit {
+    pub max_pauses: usize,
+}
+
+impl Default for PauseLimit {
+    #[inline]
+    fn default() -> Self {
+        Self { max_pauses: 3 }
+    }
+}
+
+impl MelodyRule for PauseLimit {
+    #[inline]
+    fn name(&self) -> &str {
+        "pauses"
+    }
+
+    #[inline]
+    fn violation(&self, lead: &Vec<NoteData>) -> Option<String> {
+        let pauses = lead.iter().filter(|note| note.delay() != 0).count();
+
+        match pauses > self.max_pauses {
+            true => Some(format!(
+                "{pauses} pauses, at most {} are allowed",
+                self.max_pauses
+            )),
+
+            false => None,
+        }
+    }
+}
+
+/// Phrases end on the degrees of the plan: the 4-th bar
+/// on the half cadence's degree, the 8-th bar on the full cadence's one.
+/// Missing bars are not checked
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PhraseEndings {
+    pub plan: CadencePlan,
+    pub key: PitchClass,
+    pub scale_notes: Vec<Note>,
+    pub time_signature: TimeSignature,
+}
+
+impl PhraseEndings {
+    #[inline]
+    pub fn new(
+        plan: CadencePlan,
+        key: PitchClass,
+        scale_notes: &[Note],
+        time_signature: TimeSignature,
+    ) -> Self {
+        Self {
+            plan,
+            key,
+            scale_notes: scale_notes.to_vec(),
+            time_signature,
+        }
+    }
+}
+
+impl MelodyRule for PhraseEndings {
+    #[inline]
+    fn name(&self) -> &str {
+        "cadence"
+    }
+
+    #[inline]
+    fn violation(&self, lead: &Vec<NoteData>) -> Option<String> {
+        let endings = bar_endings(lead, self.time_signature);
+
+        self.plan.targets().into_iter().find_map(|(bar, degree)| {
+            let pitch_class = degree_pitch_class(self.key, &self.scale_notes, degree)?;
+            let &(_, i) = endings.iter().find(|(ending_bar, _)| *ending_bar == bar)?;
+            let note = lead[i].note();
+
+            match note.midi() % 12 == pitch_class {
+                true => None,
+                false => Some(format!(
+                    "bar {bar} ends on {note:?} instead of the degree {degree}"
+                )),
+            }
+        })
+    }
+}
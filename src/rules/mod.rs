@@ -0,0 +1,246 @@
+use crate::{
+    notes::note_data::NoteData,
+    rules::builtin::{LeapLimit, LegatoRunLimit, MinLegato, NoteRepetition, OddRhythm, PauseLimit},
+};
+
+use itertools::Itertools;
+use std::{
+    fmt::{Debug, Display, Formatter},
+    sync::Arc,
+};
+
+pub mod builtin;
+
+/// Constraint that the generated lead has to satisfy
+
+pub trait MelodyRule: Debug + Send + Sync {
+    /// Short name of the rule that is used in reports
+
+    fn name(&self) -> &str;
+
+    /// Explanation of why the lead violates the rule.
+    /// Produces nothing if the rule is satisfied
+
+    fn violation(&self, lead: &Vec<NoteData>) -> Option<String>;
+
+    /// Checks if the rule looks at the neighbour notes only,
+    /// so the single phrase that violates it can not be
+    /// a part of the satisfying lead. Rules of the whole lead
+    /// (numbers of pauses, cadences, etc.) are not local
+
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+/// Rule that was violated by the lead with the explanation
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Violation {
+    pub rule: String,
+    pub message: String,
+}
+
+/// All violations of the rule set by the lead
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RuleReport {
+    pub violations: Vec<Violation>,
+}
+
+impl RuleReport {
+    /// Checks if the lead has satisfied all rules
+
+    #[inline]
+    pub fn is_satisfied(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl Display for RuleReport {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.is_satisfied() {
+            true => write!(f, "all rules are satisfied"),
+            false => write!(
+                f,
+                "{}",
+                self.violations
+                    .iter()
+                    .map(|violation| format!("{}: {}", violation.rule, violation.message))
+                    .join("; ")
+            ),
+        }
+    }
+}
+
+/// Registry of the rules that are checked together.
+/// Built-in sets reproduce filters of the melody generation
+/// and of the genetic algorithm's fitness, custom rules
+/// (implementors of [MelodyRule]) can be added to any set
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     notes::{note::Note, note_data::NoteData},
+///     rules::{builtin::LeapLimit, MelodyRule, RuleSet},
+/// };
+///
+/// #[derive(Debug)]
+/// struct StartsOnBeat;
+///
+/// impl MelodyRule for StartsOnBeat {
+///     fn name(&self) -> &str {
+///         "starts_on_beat"
+///     }
+///
+///     fn violation(&self, lead: &Vec<NoteData>) -> Option<String> {
+///         match lead.first().map(|note| note.delay()) {
+///             Some(0) | None => None,
+///             Some(delay) => Some(format!("lead starts after {delay} ticks")),
+///         }
+///     }
+/// }
+///
+/// let rules = RuleSet::new()
+///     .with_rule(LeapLimit { limit: 5 })
+///     .with_rule(StartsOnBeat);
+///
+/// let note = |midi: u8, delay| NoteData::new(Note::try_from(midi).unwrap(), 75, 0, 64, delay);
+/// let report = rules.check(&vec![note(60, 32), note(67, 0)]);
+///
+/// assert_eq!(report.violations.len(), 2);
+/// assert_eq!(report.violations[0].rule, "leap");
+/// assert!(rules.is_satisfied(&vec![note(60, 0), note(62, 0)]))
+/// ```
+
+#[derive(Clone, Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<Arc<dyn MelodyRule>>,
+}
+
+impl RuleSet {
+    /// Empty rule set that is satisfied by any lead
+
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rules that generated melodies are checked with:
+    /// note repetition, leaps, legato runs, odd rhythm and legato
+
+    #[inline]
+    pub fn generation() -> Self {
+        Self::new()
+            .with_rule(NoteRepetition::default())
+            .with_rule(LeapLimit::default())
+            .with_rule(LegatoRunLimit::default())
+            .with_rule(OddRhythm)
+            .with_rule(MinLegato::default())
+    }
+
+    /// Rules of the genetic algorithm's fitness:
+    /// note repetition, leaps, legato runs and pauses
+
+    #[inline]
+    pub fn fitness() -> Self {
+        Self::new()
+            .with_rule(NoteRepetition::default())
+            .with_rule(LeapLimit::default())
+            .with_rule(LegatoRunLimit::default())
+            .with_rule(PauseLimit::default())
+    }
+
+    /// Adds the rule to the set
+
+    #[inline]
+    pub fn with_rule(mut self, rule: impl MelodyRule + 'static) -> Self {
+        self.add(rule);
+        self
+    }
+
+    /// Adds the rule to the set
+
+    #[inline]
+    pub fn add(&mut self, rule: impl MelodyRule + 'static) {
+        self.rules.push(Arc::new(rule))
+    }
+
+    /// Removes all rules with the given name
+
+    #[inline]
+    pub fn without(mut self, name: &str) -> Self {
+        self.rules.retain(|rule| rule.name() != name);
+        self
+    }
+
+    /// Rules of the set that can be checked on the single phrase
+    /// ([MelodyRule::is_local])
+
+    #[inline]
+    pub fn local(&self) -> Self {
+        Self {
+            rules: self
+                .rules
+                .iter()
+                .filter(|rule| rule.is_local())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Names of all rules of the set
+
+    #[inline]
+    pub fn names(&self) -> Vec<&str> {
+        self.rules.iter().map(|rule| rule.name()).collect()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Checks if the lead satisfies all rules.
+    /// Stops on the first violated rule
+
+    #[inline]
+    pub fn is_satisfied(&self, lead: &Vec<NoteData>) -> bool {
+        self.rules.iter().all(|rule| rule.violation(lead).is_none())
+    }
+
+    /// Checks all rules and reports every violated one
+
+    #[inline]
+    pub fn check(&self, lead: &Vec<NoteData>) -> RuleReport {
+        RuleReport {
+            violations: self
+                .rules
+                .iter()
+                .filter_map(|rule| {
+                    rule.violation(lead).map(|message| Violation {
+                        rule: rule.name().to_string(),
+                        message,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Share of the satisfied rules in range 0..=1.
+    /// Empty set is always satisfied
+
+    #[inline]
+    pub fn satisfied_share(&self, lead: &Vec<NoteData>) -> f32 {
+        match self.rules.is_empty() {
+            true => 1.0,
+            false => 1.0 - self.check(lead).violations.len() as f32 / self.rules.len() as f32,
+        }
+    }
+}
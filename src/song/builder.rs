@@ -0,0 +1,381 @@
+use crate::{
+    error::Result,
+    melody_type::SynthwaveMelodyType,
+    midi::{
+        bpm::BPM,
+        generator::{
+            arpeggiator::{generate_arpeggio, ArpeggioPattern, ArpeggioRate},
+            arrangement::{arrange_song, Accompaniment, Arrangement},
+            bass::{generate_bass, BassPattern},
+            composer::*,
+            drums::{generate_drums, DrumPattern},
+            generator::generate_bpm,
+            harmony::{generate_harmony, ChordProgression, DEFAULT_CHORD_RANGE},
+        },
+        key_list, melody_types, mode_list,
+        resolution::Resolution,
+        scale_list, scale_notes,
+        time_signature::TimeSignature,
+    },
+    notes::note_data::NoteData,
+    rules::RuleSet,
+    song::{
+        instrument::{Effect, Instrument, Part},
+        Song, Track,
+    },
+};
+
+use ghakuf::messages::Message;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use rust_music_theory::{
+    note::PitchClass,
+    scale::{Mode, ScaleType},
+};
+
+use std::collections::HashMap;
+use strum::IntoEnumIterator;
+
+/// Number of bars in the generated loop
+const LOOP_BARS: u32 = 8;
+
+/// Builder of the [Song].
+/// Every setting that was not specified is chosen
+/// randomly with the seeded RNG, so the same seed
+/// and settings produce the same song
+///
+/// # Example
+/// ```
+/// use music_generator::{
+///     melody_type::SynthwaveMelodyType,
+///     song::{builder::SongBuilder, instrument::{Effect, Part}},
+/// };
+///
+/// use rust_music_theory::{note::PitchClass, scale::{Mode, ScaleType}};
+///
+/// let song = SongBuilder::new()
+///     .key(PitchClass::A)
+///     .scale(ScaleType::Diatonic)
+///     .mode(Mode::Aeolian)
+///     .melody_type(SynthwaveMelodyType::ABAB)
+///     .bpm(100)
+///     .effect(Part::Lead, Effect::Chorus(60))
+///     .seed(42)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(song.tracks.len(), 5);
+/// assert_eq!(&song.to_smf()[0..4], b"MThd")
+/// ```
+
+#[derive(Clone, Debug, Default)]
+pub struct SongBuilder {
+    key: Option<PitchClass>,
+    scale: Option<ScaleType>,
+    mode: Option<Mode>,
+    melody_type: Option<SynthwaveMelodyType>,
+    bpm: Option<u64>,
+    time_signature: TimeSignature,
+    resolution: Resolution,
+    seed: Option<u64>,
+    arrangement: Option<Arrangement>,
+    instruments: HashMap<Part, Instrument>,
+    lead: Option<Vec<NoteData>>,
+    rules: Option<RuleSet>,
+}
+
+impl SongBuilder {
+    /// Constructs builder with no chosen settings
+
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn key(mut self, key: PitchClass) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    #[inline]
+    pub fn scale(mut self, scale: ScaleType) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    #[inline]
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    #[inline]
+    pub fn melody_type(mut self, melody_type: SynthwaveMelodyType) -> Self {
+        self.melody_type = Some(melody_type);
+        self
+    }
+
+    #[inline]
+    pub fn bpm(mut self, bpm: impl BPM) -> Self {
+        self.bpm = Some(bpm.as_u64());
+        self
+    }
+
+    #[inline]
+    pub fn time_signature(mut self, time_signature: TimeSignature) -> Self {
+        self.time_signature = time_signature;
+        self
+    }
+
+    /// Time base of the produced file
+
+    #[inline]
+    pub fn resolution(mut self, resolution: Resolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    #[inline]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Generates the whole song with the given sections
+    /// instead of the 8 bar loop
+
+    #[inline]
+    pub fn arrangement(mut self, arrangement: Arrangement) -> Self {
+        self.arrangement = Some(arrangement);
+        self
+    }
+
+    /// Uses already generated 8 bar lead
+    /// (e.g. with the genetic algorithm) instead of generating it.
+    /// Lead has to be written in the builder's time signature
+
+    #[inline]
+    pub fn lead(mut self, lead: Vec<NoteData>) -> Self {
+        self.lead = Some(lead);
+        self
+    }
+
+    /// Rules that the generated lead has to satisfy
+    /// instead of the default ones ([RuleSet::generation])
+
+    #[inline]
+    pub fn rules(mut self, rules: RuleSet) -> Self {
+        self.rules = Some(rules);
+        self
+    }
+
+    /// Replaces the instrument of the part
+
+    #[inline]
+    pub fn instrument(mut self, part: Part, instrument: Instrument) -> Self {
+        self.instruments.insert(part, instrument);
+        self
+    }
+
+    /// Adds effect to the current instrument of the part
+
+    #[inline]
+    pub fn effect(mut self, part: Part, effect: Effect) -> Self {
+        let instrument = self
+            .instruments
+            .remove(&part)
+            .unwrap_or_else(|| part.default_instrument());
+
+        self.instruments
+            .insert(part, instrument.with_effect(effect));
+        self
+    }
+
+    /// Generates all parts of the song and composes them to the tracks.
+    /// Returns error if the scale can not be constructed
+
+    #[inline]
+    pub fn build(self) -> Result<Song> {
+        // Every random decision is taken with the seeded RNG,
+        // so the same seed and settings produce the same song
+
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let key = self
+            .key
+            .unwrap_or_else(|| random_choice(key_list(), &mut rng));
+        let scale = self
+            .scale
+            .unwrap_or_else(|| random_choice(scale_list(), &mut rng));
+        let mode = self
+            .mode
+            .unwrap_or_else(|| random_choice(mode_list(), &mut rng));
+        let melody_type = self
+            .melody_type
+            .unwrap_or_else(|| random_choice(melody_types(), &mut rng));
+
+        // Picking all notes in 4 and 5 octaves.
+        // These notes help to construct
+        // both lead melody and chords in harmony
+
+        let scale_notes = scale_notes(key, scale, mode, 4..=5)?;
+
+        let bpm = self.bpm.unwrap_or_else(|| generate_bpm(&mut rng).as_u64());
+
+        let time_signature = self.time_signature;
+
+        let (lead, generation) = match self.lead {
+            Some(lead) => (lead, None),
+            None => {
+                let generation = melody_type.generate_synthwave_melody_with_rules(
+                    key,
+                    &scale_notes,
+                    time_signature,
+                    &self.rules.unwrap_or_else(RuleSet::generation),
+                    &mut rng,
+                )?;
+
+                (generation.lead.clone(), Some(generation))
+            }
+        };
+
+        // Harmony follows the lead's 8 bars with a chord per bar
+
+        let progression = ChordProgression::random_progression(&mut rng);
+        let harmony = generate_harmony(
+            progression,
+            &scale_notes,
+            DEFAULT_CHORD_RANGE,
+            LOOP_BARS,
+            time_signature,
+        );
+
+        // Arpeggio plays notes of the harmony's chords
+
+        let arpeggio_rate = ArpeggioRate::random_rate(&mut rng);
+        let arpeggio_pattern = ArpeggioPattern::random_pattern(&mut rng);
+        let arpeggio_octaves = rng.gen_range(1..=2);
+
+        let arpeggio = generate_arpeggio(
+            &harmony,
+            &scale_notes,
+            arpeggio_rate,
+            arpeggio_pattern,
+            arpeggio_octaves,
+            &mut rng,
+        );
+
+        // Bass follows roots of the harmony below the lead
+
+        let bass_pattern = BassPattern::random_pattern(&mut rng);
+        let bass = generate_bass(&harmony, &lead, bass_pattern, time_signature);
+
+        // Drums are played on the same 8 bar grid
+
+        let drum_pattern = DrumPattern::random_pattern(&mut rng);
+        let drums = generate_drums(drum_pattern, LOOP_BARS, time_signature, &mut rng);
+
+        let accompaniment = Accompaniment {
+            progression,
+            arpeggio_rate,
+            arpeggio_pattern,
+            arpeggio_octaves,
+            bass_pattern,
+            drum_pattern,
+        };
+
+        // Either the 8 bar loop or the whole song is composed.
+        // Song's notes have absolute start time,
+        // so all its tracks are composed with the polyphonic composer
+
+        let (parts, markers) = match &self.arrangement {
+            None => (
+                vec![
+                    compose_lead_from_generated(lead.clone(), compose_note),
+                    compose_harmony_from_generated(harmony.clone()),
+                    compose_notes_on_channel(arpeggio, Part::Arpeggio.channel()),
+                    compose_notes_on_channel(bass, Part::Bass.channel()),
+                    compose_polyphonic(drums, Part::Drums.channel()),
+                ],
+                Vec::new(),
+            ),
+
+            Some(arrangement) => {
+                let song = arrange_song(
+                    arrangement,
+                    melody_type,
+                    &lead,
+                    &scale_notes,
+                    time_signature,
+                    accompaniment,
+                    &mut rng,
+                );
+
+                let markers = arrangement
+                    .section_starts(time_signature)
+                    .into_iter()
+                    .map(|(start, section)| (start, section.kind.name().to_string()))
+                    .collect();
+
+                (
+                    vec![
+                        compose_polyphonic(song.lead, Part::Lead.channel()),
+                        compose_polyphonic(song.harmony, Part::Harmony.channel()),
+                        compose_polyphonic(song.arpeggio, Part::Arpeggio.channel()),
+                        compose_polyphonic(song.bass, Part::Bass.channel()),
+                        compose_polyphonic(song.drums, Part::Drums.channel()),
+                    ],
+                    compose_markers(markers),
+                )
+            }
+        };
+
+        // Notes are generated in the fixed resolution
+        // and rescaled to the time base of the output file
+
+        let resolution = self.resolution;
+        let rescale =
+            |messages: Vec<Message>| rescale_messages(messages, Resolution::GENERATION, resolution);
+
+        let mut instruments = self.instruments;
+
+        let tracks = Part::iter()
+            .zip(parts)
+            .map(|(part, messages)| Track {
+                part,
+                instrument: instruments
+                    .remove(&part)
+                    .unwrap_or_else(|| part.default_instrument()),
+                messages: rescale(messages),
+            })
+            .collect();
+
+        Ok(Song {
+            key,
+            scale,
+            mode,
+            melody_type,
+            bpm,
+            time_signature,
+            resolution,
+            seed,
+            accompaniment,
+            arrangement: self.arrangement,
+            scale_notes,
+            lead,
+            generation,
+            harmony,
+            markers: rescale(markers),
+            tracks,
+        })
+    }
+}
+
+/// Picks random value from the list of supported ones
+
+#[inline]
+fn random_choice<T: Copy>(list: Vec<T>, rng: &mut impl Rng) -> T {
+    list[rng.gen_range(0..list.len())]
+}
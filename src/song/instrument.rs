@@ -0,0 +1,161 @@
+use crate::midi::generator::{
+    composer::{compose_control_change, compose_program_change},
+    drums::DRUMS_CHANNEL,
+};
+
+use ghakuf::messages::Message;
+use strum_macros::EnumIter;
+
+/// Maximum value of the program and the controller in MIDI
+const MAX_MIDI_VALUE: u8 = 127;
+
+/// Part of the song, written as the separate track
+/// on its own channel
+
+#[derive(EnumIter, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Part {
+    Lead,
+    Harmony,
+    Arpeggio,
+    Bass,
+    Drums,
+}
+
+impl Part {
+    /// MIDI channel of the part.
+    /// Drums are played on the GM percussion channel
+    ///
+    /// # Example
+    /// ```
+    /// use music_generator::song::instrument::Part;
+    /// assert_eq!(Part::Bass.channel(), 3);
+    /// assert_eq!(Part::Drums.channel(), 9)
+    /// ```
+
+    #[inline]
+    pub fn channel(&self) -> u8 {
+        match self {
+            Part::Lead => 0,
+            Part::Harmony => 1,
+            Part::Arpeggio => 2,
+            Part::Bass => 3,
+            Part::Drums => DRUMS_CHANNEL,
+        }
+    }
+
+    /// Instrument that is used when no other was chosen.
+    /// Drums keep the channel's default drum kit
+
+    #[inline]
+    pub fn default_instrument(&self) -> Instrument {
+        let instrument = |program, effects| Instrument { program, effects };
+
+        match self {
+            Part::Lead => instrument(Some(80), vec![Effect::Reverb(120), Effect::Chorus(90)]),
+            Part::Harmony => instrument(Some(89), vec![Effect::Reverb(100)]),
+            Part::Arpeggio => instrument(Some(81), vec![Effect::Reverb(80)]),
+            Part::Bass => instrument(Some(38), Vec::new()),
+            Part::Drums => instrument(None, vec![Effect::Reverb(70)]),
+        }
+    }
+}
+
+/// Effect of the channel with its level in range 0..=127.
+/// Greater levels are clamped to 127
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Effect {
+    Reverb(u8),
+    Chorus(u8),
+}
+
+impl Effect {
+    /// Number of the MIDI controller of the effect
+
+    #[inline]
+    pub fn control(&self) -> u8 {
+        match self {
+            Effect::Reverb(_) => 91,
+            Effect::Chorus(_) => 93,
+        }
+    }
+
+    /// Level of the effect
+
+    #[inline]
+    pub fn level(&self) -> u8 {
+        match self {
+            Effect::Reverb(level) | Effect::Chorus(level) => (*level).min(MAX_MIDI_VALUE),
+        }
+    }
+}
+
+/// Instrument of the track: GM program with the channel effects.
+/// Instrument without program keeps the channel's default one
+///
+/// # Example
+/// ```
+/// use music_generator::song::instrument::{Effect, Instrument};
+///
+/// let pad = Instrument::new(89).unwrap().with_effect(Effect::Reverb(100));
+/// assert_eq!(pad.program(), Some(89));
+/// assert_eq!(pad.messages(1).len(), 2);
+/// assert!(Instrument::new(128).is_none())
+/// ```
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Instrument {
+    program: Option<u8>,
+    effects: Vec<Effect>,
+}
+
+impl Instrument {
+    /// Constructs instrument with the given GM program.
+    /// Program has to be in range 0..=127
+
+    #[inline]
+    pub fn new(program: u8) -> Option<Self> {
+        match program <= MAX_MIDI_VALUE {
+            true => Some(Self {
+                program: Some(program),
+                effects: Vec::new(),
+            }),
+
+            false => None,
+        }
+    }
+
+    /// Adds effect to the instrument
+
+    #[inline]
+    pub fn with_effect(mut self, effect: Effect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    #[inline]
+    pub fn program(&self) -> Option<u8> {
+        self.program
+    }
+
+    #[inline]
+    pub fn effects(&self) -> &Vec<Effect> {
+        &self.effects
+    }
+
+    /// Program change and effects' control change
+    /// events that are placed at the start of the track
+
+    #[inline]
+    pub fn messages(&self, channel: u8) -> Vec<Message> {
+        self.program
+            .map(|program| compose_program_change(channel, program))
+            .into_iter()
+            .chain(
+                self.effects.iter().map(|effect| {
+                    compose_control_change(channel, effect.control(), effect.level())
+                }),
+            )
+            .collect()
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{
+    error::Result,
+    melody_type::{MelodyGeneration, SynthwaveMelodyType},
+    midi::{
+        generator::{
+            arrangement::{Accompaniment, Arrangement},
+            composer::{compose_end_of_track, compose_tempo, compose_time_signature},
+        },
+        resolution::Resolution,
+        smf::to_smf_bytes,
+        time_signature::TimeSignature,
+    },
+    notes::{note::Note, note_data::NoteData, ChordData},
+    song::instrument::{Instrument, Part},
+};
+
+use ghakuf::messages::Message;
+use rust_music_theory::{
+    note::PitchClass,
+    scale::{Mode, ScaleType},
+};
+
+use std::path::Path;
+
+pub mod builder;
+pub mod instrument;
+
+/// Single part of the song played by the instrument on its own channel.
+/// Messages are already in the resolution of the song
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Track {
+    pub part: Part,
+    pub instrument: Instrument,
+    pub messages: Vec<Message>,
+}
+
+impl Track {
+    /// All messages of the track:
+    /// instrument's setup, notes and the end of the track
+
+    #[inline]
+    pub fn to_messages(&self) -> Vec<Message> {
+        self.instrument
+            .messages(self.part.channel())
+            .into_iter()
+            .chain(self.messages.iter().cloned())
+            .chain(std::iter::once(compose_end_of_track()))
+            .collect()
+    }
+}
+
+/// Generated song: all settings that were used
+/// (including the randomly chosen ones), the generated
+/// lead and harmony and the composed tracks.
+/// Constructed with the [builder::SongBuilder]
+
+#[derive(Clone, Debug)]
+pub struct Song {
+    pub key: PitchClass,
+    pub scale: ScaleType,
+    pub mode: Mode,
+    pub melody_type: SynthwaveMelodyType,
+    pub bpm: u64,
+    pub time_signature: TimeSignature,
+    pub resolution: Resolution,
+    pub seed: u64,
+    pub accompaniment: Accompaniment,
+    pub arrangement: Option<Arrangement>,
+    pub scale_notes: Vec<Note>,
+    pub lead: Vec<NoteData>,
+
+    /// Outcome of the lead's generation with the rules.
+    /// Absent if the lead was given to the builder
+    pub generation: Option<MelodyGeneration>,
+
+    pub harmony: Vec<ChordData>,
+    pub markers: Vec<Message>,
+    pub tracks: Vec<Track>,
+}
+
+impl Song {
+    /// Tempo track with the tempo, the time signature
+    /// and sections' markers of the song
+
+    #[inline]
+    pub fn tempo_track(&self) -> Vec<Message> {
+        vec![
+            compose_tempo(self.bpm),
+            compose_time_signature(self.time_signature),
+        ]
+        .into_iter()
+        .chain(self.markers.iter().cloned())
+        .chain(std::iter::once(compose_end_of_track()))
+        .collect()
+    }
+
+    /// Messages of all tracks, starting from the tempo track.
+    /// Tracks are separated with [Message::TrackChange],
+    /// so the result can be passed to the [ghakuf::writer::Writer]
+
+    #[inline]
+    pub fn messages(&self) -> Vec<Message> {
+        let tracks = self
+            .tracks
+            .iter()
+            .flat_map(|track| std::iter::once(Message::TrackChange).chain(track.to_messages()));
+
+        self.tempo_track().into_iter().chain(tracks).collect()
+    }
+
+    /// Serializes song to the Standard MIDI File
+
+    #[inline]
+    pub fn to_smf(&self) -> Vec<u8> {
+        let tracks = std::iter::once(self.tempo_track())
+            .chain(self.tracks.iter().map(Track::to_messages))
+            .collect::<Vec<_>>();
+
+        to_smf_bytes(&tracks, self.resolution)
+    }
+
+    /// Writes song to the Standard MIDI File with the given path
+
+    #[inline]
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_smf())?)
+    }
+}